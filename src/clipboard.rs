@@ -1,45 +1,247 @@
-use clipboard_win::{formats, get_clipboard, is_format_avail, set_clipboard, SysResult};
-use std::thread;
+use clipboard_win::{
+    formats, get_clipboard, is_format_avail, options, raw, set_clipboard, Clipboard, SysResult,
+};
+use lazy_static::lazy_static;
+use std::num::NonZeroU32;
+use std::path::Path;
+use std::sync::Mutex;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 use windows::Win32::UI::Input::KeyboardAndMouse::{
-    keybd_event, KEYBD_EVENT_FLAGS, VK_CONTROL, VK_V,
+    keybd_event, KEYBD_EVENT_FLAGS, VIRTUAL_KEY, VK_CONTROL, VK_V,
 };
 
+use crate::config::FileDropMode;
 use crate::keyboard_hook::{set_hook, unhook};
+use crate::shutdown::join_with_timeout;
+use crate::win_api;
+
+lazy_static! {
+    static ref RESTORE_THREADS: Mutex<Vec<JoinHandle<()>>> = Mutex::new(Vec::new());
+}
+
+/// The clipboard content in the preserved formats from before pasting, so it can be restored afterwards.
+struct ClipboardSnapshot {
+    formats: Vec<(u32, Vec<u8>)>,
+}
+
+impl ClipboardSnapshot {
+    fn take(format_names: &[String]) -> SysResult<Self> {
+        let _clipboard = Clipboard::new_attempts(10)?;
+        let mut formats = Vec::new();
+        for format in format_names.iter().filter_map(|name| get_format_id(name)) {
+            if raw::is_format_avail(format) {
+                // Formats that can't be read are skipped, so the others are still restored
+                let mut data = Vec::new();
+                if raw::get_vec(format, &mut data).is_ok() {
+                    formats.push((format, data));
+                }
+            }
+        }
+        Ok(ClipboardSnapshot { formats })
+    }
 
-pub fn paste_path(path: String) -> Result<(), String> {
-    match get_clipboard_text() {
-        Ok(original_path) => {
-            set_clipboard_text(&path).map_err(|e| e.to_string())?;
+    fn restore(&self) -> SysResult<()> {
+        let _clipboard = Clipboard::new_attempts(10)?;
+        raw::empty()?;
+        for (format, data) in &self.formats {
+            raw::set_without_clear(*format, data)?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the id of a standard format like `CF_UNICODETEXT`, or of the registered format with the given name.
+/// Only formats stored in global memory can be preserved, so `CF_BITMAP` is preserved as `CF_DIB`.
+fn get_format_id(name: &str) -> Option<u32> {
+    match name {
+        "CF_TEXT" => Some(formats::CF_TEXT),
+        "CF_UNICODETEXT" => Some(formats::CF_UNICODETEXT),
+        "CF_HDROP" => Some(formats::CF_HDROP),
+        // Windows provides every bitmap on the clipboard as a DIB as well
+        "CF_DIB" | "CF_BITMAP" => Some(formats::CF_DIB),
+        "CF_DIBV5" => Some(formats::CF_DIBV5),
+        // Commonly used registered formats, which have no constant
+        "CF_HTML" => raw::register_format("HTML Format").map(NonZeroU32::get),
+        "CF_RTF" => raw::register_format("Rich Text Format").map(NonZeroU32::get),
+        _ => raw::register_format(name).map(NonZeroU32::get),
+    }
+}
+
+pub fn paste_path(
+    path: String,
+    file_drop: FileDropMode,
+    preserved_formats: &[String],
+    restore_delay: Duration,
+) -> Result<(), String> {
+    match ClipboardSnapshot::take(preserved_formats).map_err(|e| e.to_string()) {
+        Ok(original_content) => {
+            set_paste_content(&path, file_drop).map_err(|e| e.to_string())?;
+            let sequence_number = raw::seq_num();
             simulate_paste();
-            thread::spawn(move || {
-                // The simulated keypresses take some time to register, so we wait a bit before restoring the clipboard
-                thread::sleep(std::time::Duration::from_millis(100));
-                let _ = set_clipboard_text(&original_path);
+            let handle = thread::spawn(move || {
+                // The simulated keypresses take some time to register, so we wait a bit before restoring the clipboard.
+                // There is no reliable signal that the target application has read the clipboard. Reading doesn't
+                // change the sequence number, and with delayed rendering the content is requested by clipboard
+                // history and clipboard managers as soon as it is set, long before the paste is handled.
+                thread::sleep(restore_delay);
+                // Something copied in the meantime is newer than the original content and is kept
+                if raw::seq_num() == sequence_number {
+                    let _ = original_content.restore();
+                }
             });
+
+            let mut restore_threads = RESTORE_THREADS.lock().unwrap();
+            restore_threads.retain(|handle| !handle.is_finished());
+            restore_threads.push(handle);
         }
         Err(e) => return Err(e),
     }
     Ok(())
 }
 
+/// Puts the path on the clipboard like `paste_path` would, but leaves it there without pasting it.
+pub fn copy_path(path: &str, file_drop: FileDropMode) -> Result<(), String> {
+    set_paste_content(path, file_drop).map_err(|e| e.to_string())
+}
+
+/// Waits for pending clipboard restores so the user's clipboard isn't left holding a converted path.
+pub fn join_restore_threads(timeout: Duration) {
+    let deadline = Instant::now() + timeout;
+    let handles: Vec<_> = RESTORE_THREADS.lock().unwrap().drain(..).collect();
+    for handle in handles {
+        join_with_timeout(handle, deadline.saturating_duration_since(Instant::now()));
+    }
+}
+
 pub fn get_clipboard_text() -> Result<String, String> {
     if !is_format_avail(formats::CF_UNICODETEXT) {
         return Err("Clipboard does not support unicode text.".to_string());
     }
-    get_clipboard(formats::Unicode).map_err(|e| e.to_string())
+    // Read as raw data, since `formats::Unicode` silently replaces invalid UTF-16
+    let data: Vec<u8> =
+        get_clipboard(formats::RawData(formats::CF_UNICODETEXT)).map_err(|e| e.to_string())?;
+    decode_unicode_text(&data)
+}
+
+/// Decodes `CF_UNICODETEXT` data up to its terminator. Text with lone surrogates is rejected,
+/// since a lossily decoded path would be converted into a different path than the one copied.
+fn decode_unicode_text(data: &[u8]) -> Result<String, String> {
+    let units: Vec<u16> = data
+        .chunks_exact(2)
+        .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+        .take_while(|unit| *unit != 0)
+        .collect();
+    String::from_utf16(&units).map_err(|_| "Clipboard text is not valid UTF-16.".to_string())
+}
+
+pub fn get_clipboard_files() -> Result<Vec<String>, String> {
+    if !is_format_avail(formats::CF_HDROP) {
+        return Err("Clipboard does not contain files.".to_string());
+    }
+    get_clipboard(formats::FileList).map_err(|e| e.to_string())
+}
+
+/// Returns the copied text, or the path of a single file copied in Explorer.
+/// Copied shortcuts (`.lnk`) are resolved to their target unless `keep_shortcuts` is set.
+pub fn get_clipboard_path(keep_shortcuts: bool) -> Result<String, String> {
+    if is_format_avail(formats::CF_UNICODETEXT) {
+        return get_clipboard_text();
+    }
+
+    let [file] = <[String; 1]>::try_from(get_clipboard_files()?)
+        .map_err(|_| "Clipboard contains more than one file.".to_string())?;
+    let is_shortcut = Path::new(&file)
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("lnk"));
+    if is_shortcut && !keep_shortcuts {
+        // Broken shortcuts are still converted as they are
+        return Ok(win_api::resolve_shortcut(&file).unwrap_or(file));
+    }
+    Ok(file)
+}
+
+/// Adds the text to the end of the clipboard text, separated by the separator.
+pub fn append_clipboard_text(text: &str, separator: &str) -> Result<(), String> {
+    let combined = match get_clipboard_text() {
+        Ok(existing) if !existing.is_empty() => format!("{existing}{separator}{text}"),
+        _ => text.to_string(),
+    };
+    set_clipboard_text(&combined).map_err(|e| e.to_string())
 }
 
 pub fn set_clipboard_text(text: &str) -> SysResult<()> {
     set_clipboard(formats::Unicode, text)
 }
 
-fn simulate_paste() {
-    unhook();
+/// Puts the files on the clipboard as `CF_HDROP`, like copying them in Explorer.
+/// If given, the text is added as well for applications that don't accept files.
+pub fn set_clipboard_files(paths: &[&str], text: Option<&str>) -> SysResult<()> {
+    let _clipboard = Clipboard::new_attempts(10)?;
+    raw::empty()?;
+    raw::set_file_list_with(paths, options::NoClear)?;
+    if let Some(text) = text {
+        raw::set_string_with(text, options::NoClear)?;
+    }
+    Ok(())
+}
+
+fn set_paste_content(path: &str, file_drop: FileDropMode) -> SysResult<()> {
+    // Only existing files can be dropped, everything else is pasted as text
+    if file_drop == FileDropMode::Disabled || !Path::new(path).exists() {
+        return set_clipboard_text(path);
+    }
+
+    let text = (file_drop == FileDropMode::WithText).then_some(path);
+    set_clipboard_files(&[path], text)
+}
+
+/// Sends Ctrl+V to the active window. Needs to be called on the keyboard hook thread.
+pub fn simulate_paste() {
+    simulate_key_press(&[VK_CONTROL], VK_V);
+}
+
+/// Sends the key to the active window while pressing the given modifiers, which should only include
+/// the modifiers that aren't held already. Needs to be called on the keyboard hook thread.
+pub fn simulate_key_press(modifiers: &[VIRTUAL_KEY], key: VIRTUAL_KEY) {
+    // Without a hook, e.g. for `--paste`, there is nothing to reinstall afterwards
+    let hooked = unhook();
     unsafe {
-        keybd_event(VK_CONTROL.0 as u8, 0, KEYBD_EVENT_FLAGS(0), 0);
-        keybd_event(VK_V.0 as u8, 0, KEYBD_EVENT_FLAGS(0), 0);
-        keybd_event(VK_V.0 as u8, 0, KEYBD_EVENT_FLAGS(2), 0);
-        keybd_event(VK_CONTROL.0 as u8, 0, KEYBD_EVENT_FLAGS(2), 0);
+        for modifier in modifiers {
+            keybd_event(modifier.0 as u8, 0, KEYBD_EVENT_FLAGS(0), 0);
+        }
+        keybd_event(key.0 as u8, 0, KEYBD_EVENT_FLAGS(0), 0);
+        keybd_event(key.0 as u8, 0, KEYBD_EVENT_FLAGS(2), 0);
+        for modifier in modifiers.iter().rev() {
+            keybd_event(modifier.0 as u8, 0, KEYBD_EVENT_FLAGS(2), 0);
+        }
+    }
+    if hooked {
+        if let Err(e) = set_hook() {
+            crate::report_error(e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_bytes(units: &[u16]) -> Vec<u8> {
+        units.iter().flat_map(|unit| unit.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn test_decode_unicode_text() {
+        let mut units: Vec<u16> = r"C:\📁".encode_utf16().collect();
+        units.extend([0, 0]);
+        assert_eq!(
+            decode_unicode_text(&to_bytes(&units)),
+            Ok(r"C:\📁".to_string())
+        );
+
+        // A high surrogate without the low surrogate that should follow it
+        let lone_surrogate = [b'C' as u16, b':' as u16, b'\\' as u16, 0xD83D, 0];
+        assert!(decode_unicode_text(&to_bytes(&lone_surrogate)).is_err());
     }
-    set_hook();
 }
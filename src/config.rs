@@ -0,0 +1,631 @@
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::path_selection::OptionKind;
+use crate::system;
+
+/// What gets pasted when the selection is committed without cycling through the options.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CommitUnchangedBehavior {
+    /// The conversion into the path's own format
+    #[default]
+    ConvertedDefault,
+    /// The clipboard text exactly as it was copied
+    LiteralOriginal,
+}
+
+/// What happens with the selected path when the selection is committed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CommitMode {
+    #[default]
+    Paste,
+    /// Adds the path to the end of the clipboard text instead of pasting it,
+    /// to collect several paths and paste them as a list later
+    Append,
+}
+
+/// Syntax of the home folder variable for paths inside the home folder.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VariableStyle {
+    #[default]
+    Disabled,
+    /// `$HOME/...` for Unix paths
+    Posix,
+    /// `$env:USERPROFILE\...` for Windows paths
+    #[serde(rename = "powershell")]
+    PowerShell,
+    /// `%USERPROFILE%\...` for Windows paths
+    Cmd,
+}
+
+/// A global key combination like `ctrl+alt+c`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Hotkey {
+    pub ctrl: bool,
+    pub alt: bool,
+    pub shift: bool,
+    pub win: bool,
+    /// Virtual key code of the letter, digit or function key
+    pub key: u32,
+}
+
+impl Hotkey {
+    pub const CTRL_V: Hotkey = Hotkey {
+        ctrl: true,
+        alt: false,
+        shift: false,
+        win: false,
+        key: 'V' as u32,
+    };
+
+    pub fn has_modifier(&self) -> bool {
+        self.ctrl || self.alt || self.shift || self.win
+    }
+}
+
+impl FromStr for Hotkey {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut hotkey = Hotkey {
+            ctrl: false,
+            alt: false,
+            shift: false,
+            win: false,
+            key: 0,
+        };
+
+        for part in text.split('+').map(|part| part.trim().to_ascii_lowercase()) {
+            match part.as_str() {
+                "ctrl" | "control" => hotkey.ctrl = true,
+                "alt" => hotkey.alt = true,
+                "shift" => hotkey.shift = true,
+                "win" => hotkey.win = true,
+                _ if hotkey.key != 0 => return Err(format!("More than one key in {text}.")),
+                _ => hotkey.key = parse_key(&part).ok_or(format!("Unknown key {part}."))?,
+            }
+        }
+
+        if hotkey.key == 0 {
+            return Err(format!("No key in {text}."));
+        }
+        Ok(hotkey)
+    }
+}
+
+/// External search program that is launched instead of pasting when Alt is held on commit.
+#[derive(Clone, Deserialize)]
+pub struct SearchTool {
+    pub program: String,
+    /// Command line arguments, where `{path}` is replaced by the selected path.
+    pub arguments: String,
+}
+
+/// Confirmation given after the selected path was pasted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CommitFeedback {
+    #[default]
+    None,
+    Sound,
+    /// Briefly highlights the pasted option before the overlay fades out
+    Flash,
+}
+
+/// How clipboard text with multiple lines is converted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MultiLineMode {
+    #[default]
+    Disabled,
+    /// The first whitespace-separated token of each line is converted if it is a path,
+    /// e.g. for `dir /b` or log output. The rest of the line is kept as it is.
+    FirstToken,
+    /// Each line is a path of its own, e.g. for a list of copied paths. Lines that aren't paths are
+    /// kept as they are.
+    Lines,
+}
+
+/// Where the overlay is shown if the cursor position can't be determined.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverlayFallback {
+    /// The cursor position of the previous activation, or the top left corner if there was none
+    #[default]
+    LastCursor,
+    TopLeft,
+    /// The center of the primary monitor
+    Center,
+}
+
+/// Whether the pasted path is put on the clipboard as a file, for applications that only accept
+/// dropped files. This only applies to paths that exist.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileDropMode {
+    #[default]
+    Disabled,
+    /// The file and the path as text, so the application can choose
+    WithText,
+    /// Only the file
+    Only,
+}
+
+/// Custom conversion that offers the copied path rewritten by a regex, e.g. a network share as a URL.
+/// The pattern is compiled when the config is loaded, so a config with an invalid pattern is invalid.
+#[derive(Clone, Deserialize)]
+#[serde(try_from = "ConversionRuleDefinition")]
+pub struct ConversionRule {
+    /// Only shown in the rule tester
+    pub name: String,
+    /// Regex that the copied path has to match
+    pub pattern: Regex,
+    /// Output where `$1` or `${name}` are replaced by the groups captured by the pattern
+    pub template: String,
+}
+
+/// A conversion rule as it is written in the config file.
+#[derive(Deserialize)]
+struct ConversionRuleDefinition {
+    name: String,
+    pattern: String,
+    template: String,
+}
+
+/// Named set of settings that can be switched to from the tray menu.
+/// Settings that aren't given keep their value from the base config.
+#[derive(Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    pub enabled_options: Option<Vec<OptionKind>>,
+    pub default_option: Option<OptionKind>,
+    pub output_template: Option<String>,
+}
+
+#[derive(Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Upper limit for the number of options offered for a path.
+    pub max_visible_options: Option<usize>,
+    /// Options listed here are offered first and in the given order.
+    pub option_priority: Vec<OptionKind>,
+    /// Options that are offered at all. All options are offered if this isn't set.
+    pub enabled_options: Option<Vec<OptionKind>>,
+    /// Option that is selected initially instead of the path's own format, if it is available.
+    pub default_option: Option<OptionKind>,
+    /// Selects the format that was pasted last initially, if it is available. Takes precedence over
+    /// `default_option` and is forgotten when Pathte exits.
+    pub remember_last_option: bool,
+    /// Serves conversions to other processes over the `\\.\pipe\pathte` named pipe.
+    pub ipc_server: bool,
+    pub commit_unchanged_behavior: CommitUnchangedBehavior,
+    pub commit_mode: CommitMode,
+    /// Text inserted between the clipboard text and an appended path. Defaults to a line break.
+    pub append_separator: Option<String>,
+    pub commit_feedback: CommitFeedback,
+    pub file_drop: FileDropMode,
+    /// Clipboard formats like `CF_UNICODETEXT`, `CF_HTML` or `CF_HDROP` that are restored after pasting.
+    /// Text, files and images are preserved if this is empty.
+    pub preserved_formats: Vec<String>,
+    /// How long the clipboard keeps the pasted path before it is restored, in milliseconds.
+    /// Defaults to 100 ms. Slow applications may need longer to read the clipboard.
+    pub restore_delay_ms: Option<u64>,
+    /// Restore delays for applications that need a different one, keyed by executable name like
+    /// `Code.exe`. The application in the foreground when pasting is used.
+    pub app_restore_delays_ms: BTreeMap<String, u64>,
+    /// Converts copied shortcut (`.lnk`) files as they are instead of resolving their target.
+    pub keep_shortcuts: bool,
+    /// Hides the overlay from screen sharing and recordings.
+    pub exclude_from_capture: bool,
+    /// How long Ctrl+V has to be held before the overlay is shown, in milliseconds.
+    /// Shorter presses paste as usual. The overlay is shown immediately if this is 0.
+    pub activation_delay_ms: u64,
+    pub overlay_fallback: OverlayFallback,
+    /// Options can be clicked to paste them while the overlay is shown, instead of clicks passing
+    /// through to the window below.
+    pub overlay_clickable: bool,
+    /// Enlarges or shrinks the overlay on top of the display scaling, e.g. `1.25`. Defaults to 1.
+    pub overlay_scale: Option<f32>,
+    /// How long the overlay fades in and out, in milliseconds. 0 disables the animation.
+    pub overlay_fade_ms: Option<u64>,
+    pub multi_line_mode: MultiLineMode,
+    /// Shows the overlay for paths with only one option as well, to confirm that they are recognized.
+    pub show_single_option: bool,
+    /// Only offers the Unix format for Android storage paths like `/sdcard/...`.
+    pub android_paths: bool,
+    /// Converts only the file path of diff headers like `+++ b/src/main.rs`.
+    pub strip_diff_prefixes: bool,
+    /// Additionally offers only the file or folder name of the path.
+    pub offer_basename: bool,
+    /// Selects the WSL form by default for Unix paths with a drive letter like `C:/foo`.
+    pub prefer_wsl_for_drive_paths: bool,
+    /// Additionally offers Unix and WSL paths with `$`, spaces and `#` escaped for Makefiles.
+    pub makefile_escaping: bool,
+    /// Additionally offers the Cygwin form like `/cygdrive/c/foo`.
+    pub offer_cygwin: bool,
+    /// Additionally offers the MSYS form used by Git Bash like `/c/foo`.
+    pub offer_msys: bool,
+    /// Additionally offers a clickable terminal hyperlink (OSC 8) to the path.
+    pub offer_osc8_link: bool,
+    /// Additionally offers the `file://` URI of the path. Copied URIs are converted to paths as well.
+    pub offer_file_uri: bool,
+    /// Replaces environment variables like `%USERPROFILE%` in copied paths with their values.
+    pub expand_env_vars: bool,
+    /// Pastes paths that contain spaces in quotes, in double quotes for Windows paths and in single
+    /// quotes for Unix paths.
+    pub quote_paths_with_spaces: bool,
+    /// Collapses repeated separators and resolves `.` and `..` segments in copied paths.
+    pub normalize_paths: bool,
+    /// Accepts glob patterns like `C:\logs\*.txt` and keeps their wildcards when converting.
+    pub glob_patterns: bool,
+    pub home_variable: VariableStyle,
+    /// Home folder that `$HOME` refers to. Defaults to the `HOME` environment variable.
+    pub unix_home: Option<String>,
+    /// WSL distribution like `Ubuntu` whose Linux paths are additionally offered as seen from
+    /// Windows, e.g. `\\wsl$\Ubuntu\home\me`.
+    pub wsl_distro: Option<String>,
+    /// Text pasted instead of the converted path, where `{path}` is replaced by the path,
+    /// e.g. `file://{path}`. Paths are pasted as they are if this is empty.
+    pub output_template: String,
+    /// Pairs of drive letters like `["D", "E"]` that are swapped to offer the path for another layout.
+    pub drive_remap: Vec<[String; 2]>,
+    pub search_tool: SearchTool,
+    /// Hotkey like `ctrl+alt+c` that converts the clipboard to the initially selected option in place.
+    pub convert_hotkey: Option<String>,
+    /// Hotkey like `ctrl+shift+v` that shows the overlay instead of Ctrl+V.
+    pub activation_hotkey: Option<String>,
+    /// Only the convert hotkey is used and Ctrl+V isn't intercepted.
+    pub hotkey_only: bool,
+    /// Custom conversions that are offered for paths matching their pattern.
+    pub rules: Vec<ConversionRule>,
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+impl TryFrom<ConversionRuleDefinition> for ConversionRule {
+    type Error = String;
+
+    fn try_from(definition: ConversionRuleDefinition) -> Result<Self, Self::Error> {
+        let name = definition.name;
+        ConversionRule::new(name.clone(), &definition.pattern, definition.template)
+            .map_err(|e| format!("Invalid pattern of rule {name}: {e}"))
+    }
+}
+
+impl ConversionRule {
+    /// Compiles the pattern of the rule. Fails if it isn't a valid regex.
+    pub fn new(name: String, pattern: &str, template: String) -> Result<Self, String> {
+        let pattern = Regex::new(pattern).map_err(|e| e.to_string())?;
+        Ok(ConversionRule {
+            name,
+            pattern,
+            template,
+        })
+    }
+
+    /// Returns the output of the rule for the path, or `None` if the pattern doesn't match.
+    pub fn apply(&self, path: &str) -> Option<String> {
+        self.pattern.captures(path).map(|captures| {
+            let mut output = String::new();
+            captures.expand(&self.template, &mut output);
+            output
+        })
+    }
+}
+
+impl Default for SearchTool {
+    fn default() -> Self {
+        SearchTool {
+            program: "Everything.exe".to_string(),
+            arguments: "-search \"{path}\"".to_string(),
+        }
+    }
+}
+
+impl SearchTool {
+    pub fn get_arguments(&self, path: &str) -> String {
+        self.arguments.replace("{path}", path)
+    }
+}
+
+impl Config {
+    /// Loads the config file. Falls back to the defaults if there is no config file or if it can't be
+    /// read, which is logged.
+    pub fn load() -> Self {
+        let Some(path) = Config::get_config_path() else {
+            return Config::default();
+        };
+
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Config::default(),
+            Err(e) => {
+                system::log_debug_message(&format!("Failed to read {}: {e}", path.display()));
+                return Config::default();
+            }
+        };
+
+        Config::parse(&content).unwrap_or_else(|e| {
+            system::log_debug_message(&format!("Invalid config {}: {e}", path.display()));
+            Config::default()
+        })
+    }
+
+    pub fn parse(content: &str) -> Result<Self, String> {
+        toml::from_str(content).map_err(|e| e.to_string())
+    }
+
+    /// Returns this config with the settings of the named profile applied.
+    pub fn with_profile(&self, name: &str) -> Config {
+        let mut config = self.clone();
+        let Some(profile) = self.profiles.get(name) else {
+            return config;
+        };
+
+        if let Some(enabled_options) = &profile.enabled_options {
+            config.enabled_options = Some(enabled_options.clone());
+        }
+        if let Some(default_option) = profile.default_option {
+            config.default_option = Some(default_option);
+        }
+        if let Some(output_template) = &profile.output_template {
+            config.output_template = output_template.clone();
+        }
+
+        config
+    }
+
+    pub fn get_activation_delay(&self) -> Duration {
+        Duration::from_millis(self.activation_delay_ms)
+    }
+
+    pub fn get_overlay_scale(&self) -> f32 {
+        self.overlay_scale.unwrap_or(1.0).clamp(0.5, 4.0)
+    }
+
+    pub fn get_overlay_fade(&self) -> Duration {
+        Duration::from_millis(self.overlay_fade_ms.unwrap_or(80))
+    }
+
+    /// Returns the convert hotkey, if it is set and valid.
+    pub fn get_convert_hotkey(&self) -> Option<Hotkey> {
+        self.convert_hotkey.as_deref()?.parse().ok()
+    }
+
+    /// Returns the activation hotkey, or Ctrl+V if it isn't set or invalid. It needs a modifier,
+    /// since releasing the modifier pastes the selected option.
+    pub fn get_activation_hotkey(&self) -> Hotkey {
+        self.activation_hotkey
+            .as_deref()
+            .and_then(|text| text.parse::<Hotkey>().ok())
+            .filter(Hotkey::has_modifier)
+            .unwrap_or(Hotkey::CTRL_V)
+    }
+
+    /// Returns the home folder in the format of the home variable style, if it is known.
+    pub fn get_home_folder(&self) -> Option<String> {
+        match self.home_variable {
+            VariableStyle::Disabled => None,
+            VariableStyle::Posix => self.unix_home.clone().or_else(|| env::var("HOME").ok()),
+            VariableStyle::PowerShell | VariableStyle::Cmd => env::var("USERPROFILE").ok(),
+        }
+    }
+
+    pub fn get_append_separator(&self) -> &str {
+        self.append_separator.as_deref().unwrap_or("\r\n")
+    }
+
+    pub fn get_restore_delay(&self) -> Duration {
+        Duration::from_millis(self.restore_delay_ms.unwrap_or(100))
+    }
+
+    /// Returns the restore delay for pasting into the application with the given executable name.
+    /// Names are compared case-insensitively, since Windows file names are.
+    pub fn get_app_restore_delay(&self, process_name: Option<&str>) -> Duration {
+        process_name
+            .and_then(|process_name| {
+                self.app_restore_delays_ms
+                    .iter()
+                    .find(|(app, _)| app.eq_ignore_ascii_case(process_name))
+            })
+            .map_or_else(
+                || self.get_restore_delay(),
+                |(_, delay)| Duration::from_millis(*delay),
+            )
+    }
+
+    pub fn get_preserved_formats(&self) -> Vec<String> {
+        if self.preserved_formats.is_empty() {
+            return vec![
+                "CF_UNICODETEXT".to_string(),
+                "CF_HDROP".to_string(),
+                "CF_DIB".to_string(),
+            ];
+        }
+        self.preserved_formats.clone()
+    }
+
+    /// Returns the valid pairs of `drive_remap`. Letters may be given with or without a colon.
+    pub fn get_drive_remap(&self) -> Vec<(char, char)> {
+        self.drive_remap
+            .iter()
+            .filter_map(|[from, to]| Some((parse_drive_letter(from)?, parse_drive_letter(to)?)))
+            .collect()
+    }
+
+    fn get_config_path() -> Option<PathBuf> {
+        let app_data = env::var_os("APPDATA")?;
+        Some(PathBuf::from(app_data).join("Pathte").join("pathte.toml"))
+    }
+}
+
+/// Replaces `{path}` in the template with the path. The path is returned as it is if the template is empty.
+pub fn apply_output_template(template: &str, path: &str) -> String {
+    if template.is_empty() {
+        path.to_string()
+    } else {
+        template.replace("{path}", path)
+    }
+}
+
+/// Returns the virtual key code of a letter, digit or function key like `f5`.
+fn parse_key(name: &str) -> Option<u32> {
+    let mut chars = name.chars();
+    if let (Some(key), None) = (chars.next(), chars.next()) {
+        return key
+            .is_ascii_alphanumeric()
+            .then(|| key.to_ascii_uppercase() as u32);
+    }
+
+    // F1 to F24 have consecutive codes starting at 0x70
+    let number: u32 = name.strip_prefix('f')?.parse().ok()?;
+    (1..=24).contains(&number).then(|| 0x70 + number - 1)
+}
+
+fn parse_drive_letter(text: &str) -> Option<char> {
+    let mut chars = text.trim_end_matches(':').chars();
+    match (chars.next(), chars.next()) {
+        (Some(letter), None) if letter.is_ascii_alphabetic() => Some(letter),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_hotkey() {
+        let hotkey: Hotkey = "Ctrl+Alt+C".parse().unwrap();
+        assert!(hotkey.ctrl && hotkey.alt && !hotkey.shift && !hotkey.win);
+        assert_eq!(hotkey.key, 'C' as u32);
+
+        assert_eq!("win + f5".parse::<Hotkey>().unwrap().key, 0x74);
+        assert_eq!("shift+1".parse::<Hotkey>().unwrap().key, '1' as u32);
+
+        for invalid in ["ctrl+alt", "ctrl+c+d", "ctrl+f25", "ctrl+space", ""] {
+            assert!(invalid.parse::<Hotkey>().is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_activation_hotkey() {
+        let mut config = Config::default();
+        assert_eq!(config.get_activation_hotkey(), Hotkey::CTRL_V);
+
+        config.activation_hotkey = Some("ctrl+shift+v".to_string());
+        let hotkey = config.get_activation_hotkey();
+        assert!(hotkey.ctrl && hotkey.shift && !hotkey.alt && !hotkey.win);
+        assert_eq!(hotkey.key, 'V' as u32);
+
+        // Without a modifier, there would be nothing to release for pasting
+        config.activation_hotkey = Some("f8".to_string());
+        assert_eq!(config.get_activation_hotkey(), Hotkey::CTRL_V);
+        config.activation_hotkey = Some("ctrl+space".to_string());
+        assert_eq!(config.get_activation_hotkey(), Hotkey::CTRL_V);
+    }
+
+    #[test]
+    fn test_parse_config() {
+        let config = Config::parse("restore_delay_ms = 250\noption_priority = [\"wsl\"]").unwrap();
+        assert_eq!(config.get_restore_delay(), Duration::from_millis(250));
+        assert_eq!(config.option_priority, vec![OptionKind::Wsl]);
+
+        let config = Config::parse("").unwrap();
+        assert_eq!(config.get_restore_delay(), Duration::from_millis(100));
+
+        assert!(Config::parse("restore_delay_ms = \"long\"").is_err());
+        assert!(Config::parse("option_priority = [\"nonexistent\"]").is_err());
+        assert!(Config::parse("[unclosed").is_err());
+    }
+
+    #[test]
+    fn test_overlay_scale() {
+        assert_eq!(Config::default().get_overlay_scale(), 1.0);
+        let config = Config::parse("overlay_scale = 1.5").unwrap();
+        assert_eq!(config.get_overlay_scale(), 1.5);
+        let config = Config::parse("overlay_scale = 20.0").unwrap();
+        assert_eq!(config.get_overlay_scale(), 4.0);
+    }
+
+    #[test]
+    fn test_overlay_fade() {
+        assert_eq!(
+            Config::default().get_overlay_fade(),
+            Duration::from_millis(80)
+        );
+        let config = Config::parse("overlay_fade_ms = 0").unwrap();
+        assert!(config.get_overlay_fade().is_zero());
+    }
+
+    #[test]
+    fn test_app_restore_delay() {
+        let config =
+            Config::parse("restore_delay_ms = 150\n[app_restore_delays_ms]\n\"Code.exe\" = 300")
+                .unwrap();
+        assert_eq!(
+            config.get_app_restore_delay(Some("code.EXE")),
+            Duration::from_millis(300)
+        );
+        assert_eq!(
+            config.get_app_restore_delay(Some("notepad.exe")),
+            Duration::from_millis(150)
+        );
+        assert_eq!(
+            config.get_app_restore_delay(None),
+            Duration::from_millis(150)
+        );
+    }
+
+    #[test]
+    fn test_conversion_rule() {
+        let rule = ConversionRule::new(
+            "Intranet".to_string(),
+            r"^\\\\fs01\\docs\\(.*)$",
+            "https://intranet/docs/$1".to_string(),
+        )
+        .unwrap();
+        assert_eq!(
+            rule.apply(r"\\fs01\docs\a.txt"),
+            Some("https://intranet/docs/a.txt".to_string())
+        );
+        assert_eq!(rule.apply(r"C:\docs\a.txt"), None);
+
+        assert!(ConversionRule::new(String::new(), "(unclosed", String::new()).is_err());
+
+        let config = Config::parse(
+            "[[rules]]\nname = \"Home\"\npattern = '^/home/(.*)$'\ntemplate = 'C:\\Users\\$1'",
+        )
+        .unwrap();
+        assert_eq!(
+            config.rules[0].apply("/home/me"),
+            Some(r"C:\Users\me".to_string())
+        );
+
+        let error =
+            Config::parse("[[rules]]\nname = \"Broken\"\npattern = '(unclosed'\ntemplate = ''")
+                .err()
+                .unwrap();
+        assert!(error.contains("Invalid pattern of rule Broken"), "{error}");
+    }
+
+    #[test]
+    fn test_preserved_formats() {
+        let config = Config::default();
+        assert!(config
+            .get_preserved_formats()
+            .contains(&"CF_DIB".to_string()));
+
+        let config = Config::parse(r#"preserved_formats = ["CF_HTML"]"#).unwrap();
+        assert_eq!(config.get_preserved_formats(), vec!["CF_HTML"]);
+    }
+}
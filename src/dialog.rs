@@ -0,0 +1,283 @@
+use eframe::egui;
+use lazy_static::lazy_static;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use crate::config::{Config, ConversionRule};
+use crate::path_selection::{PathSelection, PathSelectionBuilder, PathSelectionInfoEntry};
+use crate::{active_config, get_restore_delay, report_error, EGUI_CONTEXT};
+use crate::{clipboard, keyboard_hook};
+
+lazy_static! {
+    static ref OPEN_DIALOG: Mutex<Option<Box<dyn DialogWindow>>> = Mutex::new(None);
+}
+
+// Time for the previously active window to regain focus after the dialog has closed
+const FOCUS_RETURN_DELAY: Duration = Duration::from_millis(200);
+
+/// A regular, focusable window that is shown in addition to the passthrough overlay.
+pub trait DialogWindow: Send {
+    fn title(&self) -> &str;
+
+    /// Renders the dialog content and returns whether the dialog should stay open.
+    fn update(&mut self, ui: &mut egui::Ui) -> bool;
+}
+
+/// Lists all options of a path with buttons to copy or paste them.
+pub struct ListSelectionDialog {
+    selection: PathSelection,
+    items: Vec<PathSelectionInfoEntry>,
+    selected: usize,
+    filter: String,
+    filter_focused: bool,
+}
+
+impl ListSelectionDialog {
+    pub fn new(selection: PathSelection) -> Self {
+        let info = selection.get_info();
+        ListSelectionDialog {
+            selection,
+            items: info.options,
+            selected: info.selected,
+            filter: String::new(),
+            filter_focused: false,
+        }
+    }
+
+    pub fn from_clipboard(config: &Config) -> Option<Self> {
+        let text = clipboard::get_clipboard_path(config.keep_shortcuts).ok()?;
+        let selection = PathSelectionBuilder::from_config(config).build(text)?;
+        Some(ListSelectionDialog::new(selection))
+    }
+}
+
+/// Whether the option's label or path contains the lowercase filter text.
+fn matches_filter(item: &PathSelectionInfoEntry, filter: &str) -> bool {
+    item.label.to_lowercase().contains(filter) || item.path.to_lowercase().contains(filter)
+}
+
+/// Returns the text that is pasted for the option, like the overlay would paste it.
+fn get_option_output(selection: &PathSelection, index: usize) -> String {
+    let mut selection = selection.clone();
+    selection.select_index(index);
+    selection.get_output_string()
+}
+
+impl DialogWindow for ListSelectionDialog {
+    fn title(&self) -> &str {
+        "Convert clipboard"
+    }
+
+    fn update(&mut self, ui: &mut egui::Ui) -> bool {
+        let filter_response = ui.add(
+            egui::TextEdit::singleline(&mut self.filter)
+                .hint_text("Filter")
+                .desired_width(f32::INFINITY),
+        );
+        if !self.filter_focused {
+            // Focus the filter once, so typing works right after opening
+            filter_response.request_focus();
+            self.filter_focused = true;
+        }
+
+        let filter = self.filter.to_lowercase();
+        let visible: Vec<bool> = self
+            .items
+            .iter()
+            .map(|item| matches_filter(item, &filter))
+            .collect();
+        if filter_response.changed() && !visible[self.selected] {
+            // Keep the selection on a visible option, so Paste doesn't paste a hidden one
+            if let Some(index) = visible.iter().position(|&x| x) {
+                self.selected = index;
+            }
+        }
+
+        egui::Grid::new("list_selection_grid")
+            .num_columns(3)
+            .show(ui, |ui| {
+                for (index, item) in self.items.iter().enumerate() {
+                    if !visible[index] {
+                        continue;
+                    }
+
+                    ui.label(&item.label);
+                    ui.selectable_value(&mut self.selected, index, &item.path);
+                    if ui.button("Copy").clicked() {
+                        let output = get_option_output(&self.selection, index);
+                        let _ = clipboard::set_clipboard_text(&output);
+                    }
+                    ui.end_row();
+                }
+            });
+
+        ui.separator();
+
+        let mut open = true;
+        ui.horizontal(|ui| {
+            // Nothing is visible to paste if the filter matches no option
+            if ui
+                .add_enabled(visible[self.selected], egui::Button::new("Paste"))
+                .clicked()
+            {
+                let path = get_option_output(&self.selection, self.selected);
+                thread::spawn(move || {
+                    thread::sleep(FOCUS_RETURN_DELAY);
+                    keyboard_hook::run_on_hook_thread(Box::new(move || {
+                        let config = active_config();
+                        if let Err(e) = clipboard::paste_path(
+                            path,
+                            config.file_drop,
+                            &config.get_preserved_formats(),
+                            get_restore_delay(&config),
+                        ) {
+                            report_error(format!("Pasting the path failed: {e}"));
+                        }
+                    }));
+                });
+                open = false;
+            }
+            if ui.button("Close").clicked() {
+                open = false;
+            }
+        });
+
+        open
+    }
+}
+
+/// Shows which conversion rules match a sample path and what they produce, to debug their patterns.
+/// A draft rule can be tried out as well before it is added to the config.
+pub struct RuleTesterDialog {
+    sample: String,
+    draft_pattern: String,
+    draft_template: String,
+    /// Compiled whenever the draft pattern or template is edited, unless the pattern is empty
+    draft: Option<Result<ConversionRule, String>>,
+}
+
+impl RuleTesterDialog {
+    pub fn new() -> Self {
+        RuleTesterDialog {
+            sample: String::new(),
+            draft_pattern: String::new(),
+            draft_template: String::new(),
+            draft: None,
+        }
+    }
+}
+
+fn show_rule_result(ui: &mut egui::Ui, rule: &ConversionRule, sample: &str) {
+    ui.label(&rule.name);
+    match rule.apply(sample) {
+        Some(output) => {
+            ui.label("Matches");
+            ui.label(output);
+        }
+        None => {
+            ui.label("No match");
+            ui.label("");
+        }
+    }
+    ui.end_row();
+}
+
+impl DialogWindow for RuleTesterDialog {
+    fn title(&self) -> &str {
+        "Test conversion rules"
+    }
+
+    fn update(&mut self, ui: &mut egui::Ui) -> bool {
+        ui.add(
+            egui::TextEdit::singleline(&mut self.sample)
+                .hint_text("Sample path")
+                .desired_width(f32::INFINITY),
+        );
+        let draft_changed = ui
+            .horizontal(|ui| {
+                let pattern = ui
+                    .add(egui::TextEdit::singleline(&mut self.draft_pattern).hint_text("Pattern"));
+                let template = ui.add(
+                    egui::TextEdit::singleline(&mut self.draft_template).hint_text("Template"),
+                );
+                pattern.changed() || template.changed()
+            })
+            .inner;
+        if draft_changed {
+            self.draft = (!self.draft_pattern.is_empty()).then(|| {
+                ConversionRule::new(
+                    "Draft".to_string(),
+                    &self.draft_pattern,
+                    self.draft_template.clone(),
+                )
+            });
+        }
+
+        ui.separator();
+
+        let sample = self.sample.trim();
+        let config = active_config();
+        egui::Grid::new("rule_tester_grid")
+            .num_columns(3)
+            .show(ui, |ui| {
+                match &self.draft {
+                    Some(Ok(draft)) => show_rule_result(ui, draft, sample),
+                    Some(Err(e)) => {
+                        ui.label("Draft");
+                        ui.label("Invalid pattern");
+                        ui.label(e);
+                        ui.end_row();
+                    }
+                    None => {}
+                }
+                for rule in &config.rules {
+                    show_rule_result(ui, rule, sample);
+                }
+            });
+        if config.rules.is_empty() {
+            ui.label("There are no rules in the config yet.");
+        }
+
+        ui.separator();
+
+        !ui.button("Close").clicked()
+    }
+}
+
+pub fn open(dialog: impl DialogWindow + 'static) {
+    *OPEN_DIALOG.lock().unwrap() = Some(Box::new(dialog));
+
+    if let Some(ctx) = EGUI_CONTEXT.lock().unwrap().as_ref() {
+        ctx.request_repaint();
+    }
+}
+
+/// Shows the open dialog, if any, in its own viewport. Needs to be called on every update.
+pub fn show(ctx: &egui::Context) {
+    let mut open_dialog = OPEN_DIALOG.lock().unwrap();
+    let Some(dialog) = open_dialog.as_mut() else {
+        return;
+    };
+
+    let mut open = true;
+    ctx.show_viewport_immediate(
+        egui::ViewportId::from_hash_of("pathte_dialog"),
+        egui::ViewportBuilder::default()
+            .with_title(dialog.title())
+            .with_inner_size([600.0, 300.0]),
+        |ctx, _class| {
+            egui::CentralPanel::default().show(ctx, |ui| {
+                open = dialog.update(ui);
+            });
+
+            if ctx.input(|i| i.viewport().close_requested()) {
+                open = false;
+            }
+        },
+    );
+
+    if !open {
+        *open_dialog = None;
+    }
+}
@@ -0,0 +1,132 @@
+//! C ABI for using the path conversion from other languages.
+//!
+//! Build the DLL with `cargo rustc --lib --release --features ffi --crate-type cdylib`.
+//! All strings are NUL-terminated UTF-16 and owned by the caller: the input is only read during the
+//! call and the output is written into the caller's buffer, so nothing has to be freed afterwards.
+
+use std::slice;
+
+use crate::path::{self, ConvertError, PathType};
+
+pub const PATHTE_OK: i32 = 0;
+pub const PATHTE_ERROR_NULL_POINTER: i32 = -1;
+pub const PATHTE_ERROR_INVALID_INPUT: i32 = -2;
+pub const PATHTE_ERROR_NOT_A_PATH: i32 = -3;
+pub const PATHTE_ERROR_INVALID_TARGET: i32 = -4;
+pub const PATHTE_ERROR_NOT_CONVERTIBLE: i32 = -5;
+pub const PATHTE_ERROR_BUFFER_TOO_SMALL: i32 = -6;
+
+/// Converts `input` into the `target` format (0 = Windows, 1 = Unix, 2 = WSL, 3 = Cygwin, 4 = MSYS)
+/// and writes the result including the terminating NUL into `out`, which has room for `cap` UTF-16
+/// code units.
+///
+/// Returns `PATHTE_OK` on success or one of the negative `PATHTE_ERROR_*` codes. `out` is left
+/// untouched on error.
+///
+/// # Safety
+///
+/// `input` has to point to a NUL-terminated UTF-16 string and `out` to a writable buffer of at least
+/// `cap` UTF-16 code units.
+#[no_mangle]
+pub unsafe extern "C" fn pathte_convert(
+    input: *const u16,
+    target: u32,
+    out: *mut u16,
+    cap: usize,
+) -> i32 {
+    if input.is_null() || out.is_null() {
+        return PATHTE_ERROR_NULL_POINTER;
+    }
+
+    let length = (0..).take_while(|&i| *input.add(i) != 0).count();
+    let Ok(input) = String::from_utf16(slice::from_raw_parts(input, length)) else {
+        return PATHTE_ERROR_INVALID_INPUT;
+    };
+
+    let converted = match convert(&input, target) {
+        Ok(converted) => converted,
+        Err(code) => return code,
+    };
+
+    let converted: Vec<u16> = converted.encode_utf16().chain([0]).collect();
+    if converted.len() > cap {
+        return PATHTE_ERROR_BUFFER_TOO_SMALL;
+    }
+
+    slice::from_raw_parts_mut(out, converted.len()).copy_from_slice(&converted);
+    PATHTE_OK
+}
+
+fn convert(input: &str, target: u32) -> Result<String, i32> {
+    let target = match target {
+        0 => PathType::Windows,
+        1 => PathType::Unix,
+        2 => PathType::Wsl,
+        3 => PathType::Cygwin,
+        4 => PathType::Msys,
+        _ => return Err(PATHTE_ERROR_INVALID_TARGET),
+    };
+
+    path::convert(input, target).map_err(|e| match e {
+        ConvertError::NotAPath => PATHTE_ERROR_NOT_A_PATH,
+        ConvertError::NotConvertible => PATHTE_ERROR_NOT_CONVERTIBLE,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn convert_wide(input: &str, target: u32, cap: usize) -> Result<String, i32> {
+        let input: Vec<u16> = input.encode_utf16().chain([0]).collect();
+        let mut out = vec![0u16; cap];
+
+        let status = unsafe { pathte_convert(input.as_ptr(), target, out.as_mut_ptr(), cap) };
+        if status != PATHTE_OK {
+            return Err(status);
+        }
+
+        let end = out.iter().position(|&c| c == 0).unwrap();
+        Ok(String::from_utf16(&out[..end]).unwrap())
+    }
+
+    #[test]
+    fn test_pathte_convert() {
+        assert_eq!(
+            convert_wide(r"C:\Users\test", 2, 64),
+            Ok("/mnt/c/Users/test".to_string())
+        );
+        assert_eq!(
+            convert_wide(r"C:\Users\test", 3, 64),
+            Ok("/cygdrive/c/Users/test".to_string())
+        );
+        assert_eq!(
+            convert_wide("/c/Users/test", 0, 64),
+            Ok(r"C:\Users\test".to_string())
+        );
+        assert_eq!(
+            convert_wide("/home/user", 0, 64),
+            Ok(r"\home\user".to_string())
+        );
+
+        assert_eq!(convert_wide("Users", 0, 64), Err(PATHTE_ERROR_NOT_A_PATH));
+        assert_eq!(
+            convert_wide("/home/user", 5, 64),
+            Err(PATHTE_ERROR_INVALID_TARGET)
+        );
+        assert_eq!(
+            convert_wide("/home/user", 2, 64),
+            Err(PATHTE_ERROR_NOT_CONVERTIBLE)
+        );
+
+        // The terminating NUL needs to fit as well
+        assert_eq!(
+            convert_wide("/home/user", 1, 10),
+            Err(PATHTE_ERROR_BUFFER_TOO_SMALL)
+        );
+        assert_eq!(
+            convert_wide("/home/user", 1, 11),
+            Ok("/home/user".to_string())
+        );
+    }
+}
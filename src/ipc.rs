@@ -0,0 +1,128 @@
+use std::ffi::c_void;
+use std::thread;
+use std::time::Duration;
+use windows::core::PCWSTR;
+use windows::Win32::Foundation::{CloseHandle, GetLastError, ERROR_PIPE_CONNECTED, HANDLE};
+use windows::Win32::Storage::FileSystem::{
+    FlushFileBuffers, ReadFile, WriteFile, PIPE_ACCESS_DUPLEX,
+};
+use windows::Win32::System::Pipes::{
+    ConnectNamedPipe, CreateNamedPipeW, DisconnectNamedPipe, PIPE_READMODE_MESSAGE,
+    PIPE_REJECT_REMOTE_CLIENTS, PIPE_TYPE_MESSAGE, PIPE_UNLIMITED_INSTANCES, PIPE_WAIT,
+};
+
+use crate::{path, win_api};
+
+const PIPE_NAME: &str = r"\\.\pipe\pathte";
+const BUFFER_SIZE: u32 = 64 * 1024;
+
+/// Serves conversion requests on the named pipe: a client writes a path string (UTF-8)
+/// and receives a JSON array with all of its conversions.
+pub fn start_ipc_server_thread() {
+    thread::spawn(|| loop {
+        if let Err(e) = serve_client() {
+            win_api::log_debug_message(&e);
+            // Avoid spinning if the pipe can't be created
+            thread::sleep(Duration::from_secs(1));
+        }
+    });
+}
+
+/// Waits for a client and answers its request. Only fails if the pipe can't be created, since errors
+/// of a client, e.g. one that disconnects early, don't affect the next one.
+fn serve_client() -> Result<(), String> {
+    let pipe_name: Vec<u16> = PIPE_NAME.encode_utf16().chain(std::iter::once(0)).collect();
+
+    let pipe = unsafe {
+        CreateNamedPipeW(
+            PCWSTR::from_raw(pipe_name.as_ptr()),
+            PIPE_ACCESS_DUPLEX,
+            // The pipe is meant for local automation, so it isn't reachable over the network
+            PIPE_TYPE_MESSAGE | PIPE_READMODE_MESSAGE | PIPE_WAIT | PIPE_REJECT_REMOTE_CLIENTS,
+            PIPE_UNLIMITED_INSTANCES,
+            BUFFER_SIZE,
+            BUFFER_SIZE,
+            0,
+            None,
+        )
+    };
+    if pipe.is_invalid() {
+        return Err("Failed to create named pipe.".to_string());
+    }
+
+    if let Err(e) = handle_client(pipe) {
+        win_api::log_debug_message(&format!("Pipe client failed: {e}"));
+    }
+
+    unsafe {
+        DisconnectNamedPipe(pipe);
+        CloseHandle(pipe);
+    }
+
+    Ok(())
+}
+
+fn handle_client(pipe: HANDLE) -> Result<(), String> {
+    unsafe {
+        // A client may connect between creating the pipe and waiting for it
+        if !ConnectNamedPipe(pipe, None).as_bool() && GetLastError() != ERROR_PIPE_CONNECTED {
+            return Err("Failed to connect to pipe client.".to_string());
+        }
+
+        let mut buffer = vec![0u8; BUFFER_SIZE as usize];
+        let mut bytes_read = 0u32;
+        if !ReadFile(
+            pipe,
+            Some(buffer.as_mut_ptr() as *mut c_void),
+            BUFFER_SIZE,
+            Some(&mut bytes_read),
+            None,
+        )
+        .as_bool()
+        {
+            return Err("Failed to read from pipe.".to_string());
+        }
+
+        let request = String::from_utf8_lossy(&buffer[..bytes_read as usize]);
+        let response = get_response(&request);
+
+        let mut bytes_written = 0u32;
+        if !WriteFile(
+            pipe,
+            Some(response.as_bytes()),
+            Some(&mut bytes_written),
+            None,
+        )
+        .as_bool()
+        {
+            return Err("Failed to write to pipe.".to_string());
+        }
+
+        FlushFileBuffers(pipe);
+    }
+
+    Ok(())
+}
+
+fn get_response(request: &str) -> String {
+    let path = request.trim_end_matches(['\r', '\n', '\0']);
+    serde_json::to_string(&path::convert_all(path)).unwrap_or_else(|_| "[]".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_response() {
+        let response: serde_json::Value =
+            serde_json::from_str(&get_response("C:\\Users\r\n\0")).unwrap();
+        assert_eq!(response[0]["path_type"], "windows");
+        assert_eq!(response[0]["path"], r"C:\Users");
+        assert_eq!(response[2]["path_type"], "wsl");
+        assert_eq!(response[2]["path"], "/mnt/c/Users");
+
+        assert_eq!(get_response("not a path\n"), "[]");
+        assert_eq!(get_response(""), "[]");
+    }
+}
@@ -1,53 +1,202 @@
 use lazy_static::lazy_static;
-use std::sync::Mutex;
-use std::thread;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
 use windows::Win32::Foundation::{HWND, LPARAM, LRESULT, WPARAM};
+use windows::Win32::System::Threading::GetCurrentThreadId;
+use windows::Win32::UI::Input::KeyboardAndMouse::{
+    RegisterHotKey, UnregisterHotKey, MOD_ALT, MOD_CONTROL, MOD_NOREPEAT, MOD_SHIFT, MOD_WIN,
+};
 use windows::Win32::UI::WindowsAndMessaging::{
-    CallNextHookEx, DispatchMessageW, GetMessageW, SetWindowsHookExW, UnhookWindowsHookEx, HHOOK,
-    KBDLLHOOKSTRUCT, MSG, WH_KEYBOARD_LL,
+    CallNextHookEx, DispatchMessageW, GetMessageW, PostThreadMessageW, SetWindowsHookExW,
+    UnhookWindowsHookEx, HHOOK, KBDLLHOOKSTRUCT, MSG, WH_KEYBOARD_LL, WM_APP, WM_HOTKEY, WM_QUIT,
 };
 
+use crate::config::Hotkey;
+use crate::shutdown::join_with_timeout;
+use crate::win_api;
+
 pub type KeyboardEventHandler = fn(event_type: u32, kb_struct: &KBDLLHOOKSTRUCT) -> bool;
+pub type HotkeyHandler = fn();
+type HookThreadTask = Box<dyn FnOnce() + Send>;
 
 lazy_static! {
     static ref HOOK_HANDLE: Mutex<Option<HHOOK>> = Mutex::new(None);
-    static ref EVENT_HANDLER: Mutex<Option<KeyboardEventHandler>> = Mutex::new(None);
+    static ref HOOK_THREAD: Mutex<Option<JoinHandle<()>>> = Mutex::new(None);
+    static ref HOOK_THREAD_TASKS: Mutex<Vec<HookThreadTask>> = Mutex::new(Vec::new());
 }
 
+static HOOK_THREAD_ID: AtomicU32 = AtomicU32::new(0);
+// Disabled from the tray menu to paste normally without quitting
+static ENABLED: AtomicBool = AtomicBool::new(true);
+// Read on every keystroke, so it is set once instead of being locked each time
+static EVENT_HANDLER: OnceLock<KeyboardEventHandler> = OnceLock::new();
+static HOTKEY: OnceLock<(Hotkey, HotkeyHandler)> = OnceLock::new();
+
+const WM_RUN_TASKS: u32 = WM_APP + 1;
+const MAX_HOOK_RESTARTS: usize = 5;
+const HOOK_RESTART_DELAY: Duration = Duration::from_secs(1);
+const HOTKEY_ID: i32 = 1;
+
+/// Toggles whether keystrokes are passed to the handler and returns the new state.
+/// While disabled, all keystrokes are passed on to other applications right away.
+pub fn toggle_enabled() -> bool {
+    !ENABLED.fetch_xor(true, Ordering::SeqCst)
+}
+
+/// Sets the handler that is called for every keystroke. Can only be set once.
 pub fn set_keyboard_handler(handler: KeyboardEventHandler) {
-    *EVENT_HANDLER.lock().unwrap() = Some(handler);
+    let _ = EVENT_HANDLER.set(handler);
 }
 
-pub fn set_hook() {
-    let handle = unsafe {
-        SetWindowsHookExW(WH_KEYBOARD_LL, Some(keyboard_hook_proc), None, 0)
-            .expect("Failed to set keyboard hook.")
-    };
+/// Sets a global hotkey, whose handler is called on the hook thread. Can only be set once,
+/// before the hook thread is started.
+pub fn set_hotkey(hotkey: Hotkey, handler: HotkeyHandler) {
+    let _ = HOTKEY.set((hotkey, handler));
+}
+
+fn register_hotkey(hotkey: &Hotkey) -> bool {
+    let mut modifiers = MOD_NOREPEAT;
+    for (pressed, modifier) in [
+        (hotkey.ctrl, MOD_CONTROL),
+        (hotkey.alt, MOD_ALT),
+        (hotkey.shift, MOD_SHIFT),
+        (hotkey.win, MOD_WIN),
+    ] {
+        if pressed {
+            modifiers |= modifier;
+        }
+    }
+
+    // Without a window, the hotkey is posted to this thread's message queue
+    unsafe { RegisterHotKey(HWND(0), HOTKEY_ID, modifiers, hotkey.key) }.as_bool()
+}
+
+pub fn set_hook() -> Result<(), String> {
+    let handle = unsafe { SetWindowsHookExW(WH_KEYBOARD_LL, Some(keyboard_hook_proc), None, 0) }
+        .map_err(|e| format!("Failed to set keyboard hook: {e}"))?;
 
     *HOOK_HANDLE.lock().unwrap() = Some(handle);
+    Ok(())
 }
 
-pub fn unhook() {
-    if let Some(hook) = *HOOK_HANDLE.lock().unwrap() {
+/// Removes the keyboard hook. Returns whether it was installed.
+pub fn unhook() -> bool {
+    match HOOK_HANDLE.lock().unwrap().take() {
+        Some(hook) => {
+            unsafe {
+                UnhookWindowsHookEx(hook);
+            }
+            true
+        }
+        None => false,
+    }
+}
+
+/// Starts the thread that runs the message loop for the keyboard hook, the hotkey and window events.
+/// The keyboard hook is only installed if `install_hook` is set.
+pub fn start_keyboard_hook_thread(install_hook: bool) {
+    let handle = thread::spawn(move || {
+        HOOK_THREAD_ID.store(unsafe { GetCurrentThreadId() }, Ordering::SeqCst);
+
+        // Display changes are handled on this thread since it already runs a message loop
+        let _ = win_api::create_display_change_window();
+        let _ = win_api::create_foreground_event_hook();
+
+        let hotkey_registered = HOTKEY
+            .get()
+            .is_some_and(|(hotkey, _)| register_hotkey(hotkey));
+
+        let mut result = Ok(());
+        for _ in 0..MAX_HOOK_RESTARTS {
+            // Failing to install the hook counts as a failed attempt, like an error in the message loop
+            let installed = if install_hook { set_hook() } else { Ok(()) };
+            result = installed.and_then(|()| run_message_loop());
+            unhook();
+
+            if result.is_ok() {
+                break;
+            }
+
+            // Give the system some time before the hook is re-established
+            thread::sleep(HOOK_RESTART_DELAY);
+        }
+        if let Err(e) = result {
+            crate::report_error(format!(
+                "Gave up on the keyboard hook after {MAX_HOOK_RESTARTS} attempts: {e}"
+            ));
+        }
+
+        if hotkey_registered {
+            unsafe {
+                UnregisterHotKey(HWND(0), HOTKEY_ID);
+            }
+        }
+    });
+
+    *HOOK_THREAD.lock().unwrap() = Some(handle);
+}
+
+/// Processes messages until `WM_QUIT` is received. Fails if the message queue can't be read.
+fn run_message_loop() -> Result<(), String> {
+    let mut msg = MSG::default();
+    loop {
+        // Besides zero for `WM_QUIT`, `GetMessageW` returns -1 on errors
+        match unsafe { GetMessageW(&mut msg, HWND(0), 0, 0) }.0 {
+            0 => return Ok(()),
+            -1 => return Err("Failed to get message.".to_string()),
+            _ => {}
+        }
+
+        if msg.message == WM_RUN_TASKS {
+            let tasks: Vec<_> = HOOK_THREAD_TASKS.lock().unwrap().drain(..).collect();
+            for task in tasks {
+                task();
+            }
+        } else if msg.message == WM_HOTKEY && msg.wParam.0 == HOTKEY_ID as usize {
+            if let Some((_, handler)) = HOTKEY.get() {
+                handler();
+            }
+        }
+
         unsafe {
-            UnhookWindowsHookEx(hook);
+            DispatchMessageW(&msg);
         }
     }
 }
 
-pub fn start_keyboard_hook_thread() {
-    let _ = thread::spawn(move || {
-        set_hook();
+/// Runs the task on the hook thread, which is required for anything that re-installs the hook
+/// (like simulating key presses), since the hook is bound to the thread that installed it.
+pub fn run_on_hook_thread(task: HookThreadTask) {
+    HOOK_THREAD_TASKS.lock().unwrap().push(task);
 
-        let mut msg = MSG::default();
+    let thread_id = HOOK_THREAD_ID.load(Ordering::SeqCst);
+    if thread_id != 0 {
         unsafe {
-            while GetMessageW(&mut msg, HWND(0), 0, 0).as_bool() {
-                DispatchMessageW(&msg);
-            }
+            PostThreadMessageW(thread_id, WM_RUN_TASKS, WPARAM(0), LPARAM(0));
+        }
+    }
+}
+
+/// Ends the hook thread's message loop and waits for it to remove the hook.
+/// If the thread doesn't finish in time, the hook is removed from here instead.
+pub fn stop_keyboard_hook_thread(timeout: Duration) {
+    let thread_id = HOOK_THREAD_ID.load(Ordering::SeqCst);
+    if thread_id != 0 {
+        unsafe {
+            PostThreadMessageW(thread_id, WM_QUIT, WPARAM(0), LPARAM(0));
         }
+    }
+
+    let finished = match HOOK_THREAD.lock().unwrap().take() {
+        Some(handle) => join_with_timeout(handle, timeout),
+        None => false,
+    };
 
+    if !finished {
         unhook();
-    });
+    }
 }
 
 unsafe extern "system" fn keyboard_hook_proc(
@@ -55,11 +204,11 @@ unsafe extern "system" fn keyboard_hook_proc(
     w_param: WPARAM,
     l_param: LPARAM,
 ) -> LRESULT {
-    if code >= 0 {
+    if code >= 0 && ENABLED.load(Ordering::Relaxed) {
         let kb_struct = *(l_param.0 as *const KBDLLHOOKSTRUCT);
         let event_type = w_param.0 as u32;
 
-        if let Some(handler) = *EVENT_HANDLER.lock().unwrap() {
+        if let Some(handler) = EVENT_HANDLER.get() {
             if handler(event_type, &kb_struct) {
                 // Prevent original keypress from being processed
                 return LRESULT(1);
@@ -67,11 +216,7 @@ unsafe extern "system" fn keyboard_hook_proc(
         }
     }
 
-    // Leave original keypress to be processed by the system
-    return CallNextHookEx(
-        HOOK_HANDLE.lock().unwrap().unwrap_or(HHOOK(0)),
-        code,
-        w_param,
-        l_param,
-    );
+    // Leave original keypress to be processed by the system.
+    // The hook handle is ignored by `CallNextHookEx`, so it doesn't need to be locked here.
+    CallNextHookEx(HHOOK(0), code, w_param, l_param)
 }
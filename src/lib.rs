@@ -0,0 +1,20 @@
+//! Path conversion and selection logic of Pathte, usable without the GUI. Only the lookups in
+//! [`system`] use Windows APIs and fail on other platforms, so the library builds on them as well.
+//!
+//! ```
+//! use pathte::{convert, PathType};
+//!
+//! assert_eq!(convert(r"C:\Users", PathType::Wsl).unwrap(), "/mnt/c/Users");
+//! ```
+
+pub mod config;
+pub mod path;
+pub mod path_selection;
+pub mod system;
+
+pub use path::{convert, ConvertError, PathError, PathType};
+
+#[cfg(feature = "ffi")]
+pub mod ffi;
+
+pub static APP_NAME: &str = "Pathte";
@@ -0,0 +1,257 @@
+use eframe::egui::{self, Window};
+use std::sync::mpsc::Receiver;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::path_selection::{PathSelectionInfo, PathSelectionInfoEntry};
+use crate::{active_config, commit_clicked_option, ACTIVE_PROFILE, APP_NAME, EGUI_CONTEXT};
+use crate::{dialog, keyboard_hook, shutdown, tray, win_api};
+
+const FLASH_DURATION: Duration = Duration::from_millis(150);
+const TOPMOST_INTERVAL: Duration = Duration::from_millis(250);
+const ERROR_DURATION: Duration = Duration::from_secs(3);
+// Longer paths are shortened in the middle, the full path is shown when hovering them
+const MAX_PATH_CHARS: usize = 120;
+// Used until the overlay has been shown once
+const DEFAULT_CONTENT_EXTENT: (f32, f32) = (400.0, 80.0);
+
+// Logical size of the overlay window before the overlay scale is applied
+const WINDOW_SIZE: (f32, f32) = (2560.0, 100.0);
+
+// Logical size of the options from the top left corner of the overlay, the last time it was shown
+static CONTENT_EXTENT: Mutex<(f32, f32)> = Mutex::new(DEFAULT_CONTENT_EXTENT);
+
+struct Pathte {
+    signal_receiver: Receiver<Option<PathSelectionInfo>>,
+    error_receiver: Receiver<String>,
+    current_path_selection_info: Option<PathSelectionInfo>,
+    /// The last error and when it was received, shown until `ERROR_DURATION` has passed
+    error: Option<(String, Instant)>,
+    last_interaction: Instant,
+    flash_start: Option<Instant>,
+    last_topmost: Instant,
+    /// Whether options can be clicked, so the overlay only passes clicks through while it is hidden
+    clickable: bool,
+}
+
+impl eframe::App for Pathte {
+    fn clear_color(&self, _visuals: &egui::Visuals) -> [f32; 4] {
+        egui::Rgba::TRANSPARENT.to_array()
+    }
+
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        if shutdown::is_quit_requested() {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+            return;
+        }
+
+        // Sending a viewport command on every update prevents egui from using reactive mode.
+        // To still be able to save cpu usage, we only send the command if there has been a recent interaction.
+        if self.last_interaction.elapsed() < Duration::from_secs(1)
+            || self.current_path_selection_info.is_some()
+        {
+            let passthrough = !(self.clickable && self.current_path_selection_info.is_some());
+            ctx.send_viewport_cmd(egui::ViewportCommand::MousePassthrough(passthrough));
+        }
+
+        tray::update_checks(ACTIVE_PROFILE.lock().unwrap().as_deref());
+
+        if let Ok(path_selection_info) = self.signal_receiver.try_recv() {
+            self.flash_start = path_selection_info
+                .as_ref()
+                .filter(|info| info.committed)
+                .map(|_| Instant::now());
+            self.current_path_selection_info = path_selection_info;
+            self.last_interaction = Instant::now();
+        }
+
+        if let Ok(error) = self.error_receiver.try_recv() {
+            self.error = Some((error, Instant::now()));
+            self.last_interaction = Instant::now();
+        }
+
+        if let Some((_, received)) = &self.error {
+            if received.elapsed() >= ERROR_DURATION {
+                self.error = None;
+            } else {
+                ctx.request_repaint_after(ERROR_DURATION - received.elapsed());
+            }
+        }
+
+        if let Some(flash_start) = self.flash_start {
+            if flash_start.elapsed() >= FLASH_DURATION {
+                self.flash_start = None;
+                self.current_path_selection_info = None;
+            } else {
+                ctx.request_repaint_after(FLASH_DURATION - flash_start.elapsed());
+            }
+        }
+
+        if self.current_path_selection_info.is_some() {
+            // Some fullscreen apps cover topmost windows, so the overlay is brought back to the top
+            if self.last_topmost.elapsed() >= TOPMOST_INTERVAL {
+                if let Ok(hwnd) = win_api::find_app_window() {
+                    let _ = win_api::reassert_topmost(hwnd);
+                }
+                self.last_topmost = Instant::now();
+            }
+            ctx.request_repaint_after(TOPMOST_INTERVAL);
+        }
+
+        let response = Window::new(APP_NAME)
+            .open(&mut self.current_path_selection_info.is_some())
+            .fade_in(true)
+            .fade_out(true)
+            .collapsible(false)
+            .title_bar(false)
+            .max_size([2500.0, 80.0])
+            .auto_sized()
+            .hscroll(true)
+            .fixed_pos((10.0, 10.0))
+            .resizable(false)
+            .show(ctx, |ui| {
+                egui::Grid::new("path_grid")
+                    .spacing([-5.0, 0.0])
+                    .show(ui, |ui| {
+                        if let Some(info) = &mut self.current_path_selection_info {
+                            if info.committed {
+                                ui.visuals_mut().selection.bg_fill = egui::Color32::DARK_GREEN;
+                            }
+
+                            for (index, option) in info.options.iter().enumerate() {
+                                ui.label(&option.label);
+                                let response = ui
+                                    .selectable_value(
+                                        &mut info.selected,
+                                        index,
+                                        elide_middle(&option.path, MAX_PATH_CHARS),
+                                    )
+                                    .on_hover_ui(|ui| show_option_details(ui, option));
+                                if self.clickable && !info.committed && response.clicked() {
+                                    // The selection lives on the hook thread, which pastes it
+                                    keyboard_hook::run_on_hook_thread(Box::new(move || {
+                                        commit_clicked_option(index)
+                                    }));
+                                }
+                                ui.end_row();
+                            }
+
+                            // Wrappers, suffixes and the output template change what is pasted
+                            if info.output != info.options[info.selected].path {
+                                ui.label("Paste");
+                                ui.label(
+                                    egui::RichText::new(elide_middle(&info.output, MAX_PATH_CHARS))
+                                        .strong(),
+                                )
+                                .on_hover_text(&info.output);
+                                ui.end_row();
+                            }
+                        }
+                    });
+            });
+        if let Some(response) = response {
+            // Points are scaled by the zoom factor, unlike the logical size of the window
+            let max = response.response.rect.max * ctx.zoom_factor();
+            *CONTENT_EXTENT.lock().unwrap() = (max.x, max.y);
+        }
+
+        if self.current_path_selection_info.is_none() {
+            if let Some((error, _)) = &self.error {
+                Window::new("Error")
+                    .collapsible(false)
+                    .title_bar(false)
+                    .auto_sized()
+                    .fixed_pos((10.0, 10.0))
+                    .resizable(false)
+                    .show(ctx, |ui| {
+                        ui.colored_label(ui.visuals().error_fg_color, error);
+                    });
+            }
+        }
+
+        dialog::show(ctx);
+    }
+}
+
+/// Shortens the text to at most `max_chars` characters by replacing its middle with an ellipsis,
+/// so that both the start and the file name of a long path stay visible.
+fn elide_middle(text: &str, max_chars: usize) -> String {
+    let char_count = text.chars().count();
+    if char_count <= max_chars {
+        return text.to_string();
+    }
+
+    let kept = max_chars.saturating_sub(1);
+    let head: String = text.chars().take(kept - kept / 2).collect();
+    let tail: String = text.chars().skip(char_count - kept / 2).collect();
+    format!("{head}…{tail}")
+}
+
+fn show_option_details(ui: &mut egui::Ui, option: &PathSelectionInfoEntry) {
+    ui.label(egui::RichText::new(&option.path).monospace());
+    ui.label(format!("Detected as: {}", option.source_label));
+    ui.label(if option.lossless {
+        "Converts back to the copied path"
+    } else {
+        "Does not convert back to the copied path"
+    });
+    if let Some(exists) = option.exists {
+        ui.label(if exists {
+            "Exists on disk"
+        } else {
+            "Does not exist on disk"
+        });
+    }
+    if !option.metadata.absolute {
+        ui.label("Relative to the current folder");
+    }
+}
+
+/// Returns the logical size of the options from the top left corner of the overlay window, so that
+/// the window can be placed where they are fully visible.
+pub fn content_extent() -> (f32, f32) {
+    *CONTENT_EXTENT.lock().unwrap()
+}
+
+/// Runs the overlay on the current thread until quit is requested. The overlay shows the
+/// selections received from `signal_receiver` and briefly shows errors from `error_receiver`.
+///
+/// The process is DPI aware per monitor, so egui follows the scaling of the display that the
+/// overlay is moved to. The overlay scale from the config is applied as zoom on top of that.
+/// The fade duration from the config sets egui's animation time, which the window fades use.
+pub fn run(signal_receiver: Receiver<Option<PathSelectionInfo>>, error_receiver: Receiver<String>) {
+    let config = active_config();
+    let scale = config.get_overlay_scale();
+    let fade = config.get_overlay_fade();
+    let clickable = config.overlay_clickable;
+    let options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default()
+            .with_decorations(false)
+            .with_taskbar(false)
+            .with_inner_size((WINDOW_SIZE.0 * scale, WINDOW_SIZE.1 * scale))
+            .with_position((100000.0, 100000.0))
+            .with_transparent(true)
+            .with_always_on_top(),
+        ..Default::default()
+    };
+    let _ = eframe::run_native(
+        APP_NAME,
+        options.clone(),
+        Box::new(move |cc| {
+            *EGUI_CONTEXT.lock().unwrap() = Some(cc.egui_ctx.clone());
+            cc.egui_ctx.set_zoom_factor(scale);
+            cc.egui_ctx
+                .style_mut(|style| style.animation_time = fade.as_secs_f32());
+            Ok(Box::new(Pathte {
+                signal_receiver,
+                error_receiver,
+                current_path_selection_info: None,
+                error: None,
+                last_interaction: Instant::now(),
+                flash_start: None,
+                last_topmost: Instant::now(),
+                clickable,
+            }))
+        }),
+    );
+}
@@ -1,26 +1,128 @@
 use lazy_static::lazy_static;
 use regex::Regex;
+use serde::Serialize;
+use std::env;
+use std::fmt;
 
 lazy_static! {
     static ref WSL_REGEX: Regex =
         Regex::new(r#"^/mnt/(([A-Za-z]/?$)|([A-Za-z]/[^\x00]*))$"#).unwrap();
-    static ref WINDOWS_REGEX: Regex =
-        Regex::new(r#"^([a-zA-Z]:\\?$)|([^\x00-\x1F<>:"|?*/]*\\[^\x00-\x1F<>:"|?*/]*$)"#).unwrap();
+    // A drive root, or a path with at least one backslash and an optional drive letter in front
+    static ref WINDOWS_REGEX: Regex = Regex::new(
+        r#"^(?:[a-zA-Z]:\\?|(?:[a-zA-Z]:)?[^\x00-\x1F<>:"|?*/]*\\[^\x00-\x1F<>:"|?*/]*)$"#
+    )
+    .unwrap();
+    static ref CYGWIN_REGEX: Regex =
+        Regex::new(r#"^/cygdrive/(([A-Za-z]/?$)|([A-Za-z]/[^\x00]*))$"#).unwrap();
+    static ref WINDOWS_DRIVE_REGEX: Regex = Regex::new(r"^([A-Za-z]):").unwrap();
+    static ref WSL_DRIVE_REGEX: Regex = Regex::new(r"^/mnt/([A-Za-z])").unwrap();
+    static ref CYGWIN_DRIVE_REGEX: Regex = Regex::new(r"^/cygdrive/([A-Za-z])").unwrap();
+    // Git Bash and MSYS2 mount drives at the root, e.g. `/c/Users`
+    static ref MSYS_REGEX: Regex = Regex::new(r#"^/(([A-Za-z]/?$)|([A-Za-z]/[^\x00]*))$"#).unwrap();
+    static ref MSYS_DRIVE_REGEX: Regex = Regex::new(r"^/([A-Za-z])").unwrap();
     static ref UNIX_REGEX: Regex = Regex::new(r"^[^\x00]*/[^\x00]*$").unwrap();
+    static ref ANDROID_REGEX: Regex = Regex::new(r"^/(sdcard|storage/emulated/0)(/|$)").unwrap();
+    // The file system of a WSL distribution as seen from Windows, e.g. `\\wsl$\Ubuntu\home` or
+    // `\\wsl.localhost\Ubuntu\home` since Windows 11
+    static ref WSL_UNC_REGEX: Regex =
+        Regex::new(r"^\\\\(?i:wsl\$|wsl\.localhost)\\([^\\]+)(\\.*)?$").unwrap();
+    static ref UNIX_DRIVE_REGEX: Regex = Regex::new(r"^([A-Za-z]):/").unwrap();
+    static ref DRIVE_ROOT_REGEX: Regex = Regex::new(r"^[A-Za-z]:$").unwrap();
+    static ref UNC_ROOT_REGEX: Regex = Regex::new(r"^(\\\\|//)[^\\/]").unwrap();
+    static ref DRIVE_PREFIX_REGEX: Regex = Regex::new(r"^[A-Za-z]:([\\/]|$)").unwrap();
+    static ref MOUNT_ROOT_REGEX: Regex = Regex::new(r"^/(mnt/|cygdrive/)?[A-Za-z](/|$)").unwrap();
+    // Windows environment variables like `%USERPROFILE%` or `%ProgramFiles(x86)%`
+    static ref ENV_VAR_REGEX: Regex = Regex::new(r"%([A-Za-z0-9_()]+)%").unwrap();
     static ref PROTOCOL_REGEX: Regex = Regex::new(r"(http|https|ftp|sftp|file):$").unwrap();
 }
 
 pub trait Path {
-    fn to_windows(&self) -> Result<Box<dyn Path>, String>;
-    fn to_unix(&self) -> Result<Box<dyn Path>, String>;
-    fn to_wsl(&self) -> Result<Box<dyn Path>, String>;
+    fn to_windows(&self) -> Result<Box<dyn Path>, PathError>;
+    fn to_unix(&self) -> Result<Box<dyn Path>, PathError>;
+    fn to_wsl(&self) -> Result<Box<dyn Path>, PathError>;
+    fn to_cygwin(&self) -> Result<Box<dyn Path>, PathError>;
+    fn to_msys(&self) -> Result<Box<dyn Path>, PathError>;
     fn as_string(&self) -> String;
     fn get_type(&self) -> PathType;
+
+    fn get_metadata(&self) -> PathMetadata {
+        get_metadata(&self.as_string())
+    }
+}
+
+/// Why a text isn't a path in a format, or couldn't be converted into it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PathError {
+    EmptyInput,
+    /// The text isn't a path in the given format
+    NotRecognized(PathType),
+    /// The path has no valid form in the given format, e.g. a UNC path in WSL
+    ConversionFailed(PathType),
+}
+
+impl fmt::Display for PathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PathError::EmptyInput => write!(f, "The given path is empty."),
+            PathError::NotRecognized(path_type) => {
+                write!(f, "The given path is not a {path_type:?} path.")
+            }
+            PathError::ConversionFailed(path_type) => {
+                write!(f, "The path can't be converted to a {path_type:?} path.")
+            }
+        }
+    }
+}
+
+impl std::error::Error for PathError {}
+
+/// Boxes the path that a conversion produced. A result that isn't valid in the target format means
+/// that the conversion failed, rather than that the input wasn't recognized.
+fn converted<P: Path + 'static>(result: Result<P, PathError>) -> Result<Box<dyn Path>, PathError> {
+    match result {
+        Ok(path) => Ok(Box::new(path)),
+        Err(PathError::NotRecognized(path_type)) => Err(PathError::ConversionFailed(path_type)),
+        Err(e) => Err(e),
+    }
+}
+
+/// What a path starts with.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RootKind {
+    /// A drive letter like `C:\` or `C:/`
+    Drive,
+    /// A network share like `\\server\share` or `//server/share`
+    Unc,
+    /// A drive mounted in WSL, Cygwin or MSYS like `/mnt/c`, `/cygdrive/c` or `/c`
+    Mount,
+    /// A single separator like `/home` or `\Users`
+    Separator,
+    /// Nothing, the path is relative
+    None,
+}
+
+/// Structure of a path string, so it can be reasoned about without parsing it again.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct PathMetadata {
+    /// The first separator in the path, if there is any
+    pub separator: Option<char>,
+    pub absolute: bool,
+    pub root: RootKind,
+}
+
+#[derive(Serialize)]
+pub struct Conversion {
+    pub path_type: PathType,
+    pub path: String,
 }
 
 #[derive(Clone)]
 pub struct WindowsPath {
+    /// The path without its extended-length prefix
     path: String,
+    /// Whether the path had the extended-length prefix `\\?\`, which only the Windows form keeps
+    long_path: bool,
 }
 
 #[derive(Clone)]
@@ -33,13 +135,30 @@ pub struct WslPath {
     path: String,
 }
 
+#[derive(Clone)]
+pub struct CygwinPath {
+    path: String,
+}
+
+#[derive(Clone)]
+pub struct MsysPath {
+    path: String,
+}
+
 impl WindowsPath {
-    pub fn new(path: String) -> Result<Self, String> {
-        if WindowsPath::is_windows_path(&path) {
-            Ok(WindowsPath { path })
-        } else {
-            Err("The given path is not a Windows path.".to_string())
+    pub fn new(path: String) -> Result<Self, PathError> {
+        if path.is_empty() {
+            return Err(PathError::EmptyInput);
+        }
+        if !WindowsPath::is_windows_path(&path) {
+            return Err(PathError::NotRecognized(PathType::Windows));
         }
+
+        let (path, long_path) = match WindowsPath::strip_long_path_prefix(&path) {
+            Some(stripped) => (stripped, true),
+            None => (path, false),
+        };
+        Ok(WindowsPath { path, long_path })
     }
 
     pub fn is_windows_path(path: &str) -> bool {
@@ -47,16 +166,36 @@ impl WindowsPath {
             return false;
         }
 
-        WINDOWS_REGEX.is_match(path)
+        match WindowsPath::strip_long_path_prefix(path) {
+            Some(stripped) => WINDOWS_REGEX.is_match(&stripped),
+            None => WINDOWS_REGEX.is_match(path),
+        }
+    }
+
+    /// Returns the path without the extended-length prefix `\\?\`, or `\\?\UNC\` for network
+    /// shares, if it has one.
+    fn strip_long_path_prefix(path: &str) -> Option<String> {
+        if let Some(unc_path) = path.strip_prefix(r"\\?\UNC\") {
+            Some(format!(r"\\{unc_path}"))
+        } else {
+            path.strip_prefix(r"\\?\").map(str::to_string)
+        }
+    }
+
+    /// Whether the path is on a network share like `\\server\share`, or a bare server like `\\server`.
+    pub fn is_unc(&self) -> bool {
+        self.path.starts_with(r"\\") && UNC_ROOT_REGEX.is_match(&self.path)
     }
 }
 
 impl UnixPath {
-    pub fn new(path: String) -> Result<Self, String> {
-        if UnixPath::is_unix_path(&path) {
+    pub fn new(path: String) -> Result<Self, PathError> {
+        if path.is_empty() {
+            Err(PathError::EmptyInput)
+        } else if UnixPath::is_unix_path(&path) {
             Ok(UnixPath { path })
         } else {
-            Err("The given path is not a Unix path.".to_string())
+            Err(PathError::NotRecognized(PathType::Unix))
         }
     }
 
@@ -70,11 +209,13 @@ impl UnixPath {
 }
 
 impl WslPath {
-    pub fn new(path: String) -> Result<Self, String> {
-        if WslPath::is_wsl_path(&path) {
+    pub fn new(path: String) -> Result<Self, PathError> {
+        if path.is_empty() {
+            Err(PathError::EmptyInput)
+        } else if WslPath::is_wsl_path(&path) {
             Ok(WslPath { path })
         } else {
-            Err("The given path is not a WSL path.".to_string())
+            Err(PathError::NotRecognized(PathType::Wsl))
         }
     }
 
@@ -87,34 +228,106 @@ impl WslPath {
     }
 }
 
+impl CygwinPath {
+    pub fn new(path: String) -> Result<Self, PathError> {
+        if path.is_empty() {
+            Err(PathError::EmptyInput)
+        } else if CygwinPath::is_cygwin_path(&path) {
+            Ok(CygwinPath { path })
+        } else {
+            Err(PathError::NotRecognized(PathType::Cygwin))
+        }
+    }
+
+    fn is_cygwin_path(path: &str) -> bool {
+        if path.contains("//") || path.contains("\n") {
+            return false;
+        }
+
+        CYGWIN_REGEX.is_match(path)
+    }
+}
+
+impl MsysPath {
+    pub fn new(path: String) -> Result<Self, PathError> {
+        if path.is_empty() {
+            Err(PathError::EmptyInput)
+        } else if MsysPath::is_msys_path(&path) {
+            Ok(MsysPath { path })
+        } else {
+            Err(PathError::NotRecognized(PathType::Msys))
+        }
+    }
+
+    fn is_msys_path(path: &str) -> bool {
+        if path.contains("//") || path.contains("\n") {
+            return false;
+        }
+
+        MSYS_REGEX.is_match(path)
+    }
+}
+
 impl Path for WindowsPath {
-    fn to_windows(&self) -> Result<Box<dyn Path>, String> {
+    fn to_windows(&self) -> Result<Box<dyn Path>, PathError> {
         Ok(Box::new(self.clone()))
     }
 
-    fn to_unix(&self) -> Result<Box<dyn Path>, String> {
-        let unix_path = self.path.replace('\\', "/");
-        match UnixPath::new(unix_path) {
-            Ok(path) => Ok(Box::new(path)),
-            Err(e) => Err(e),
+    fn to_unix(&self) -> Result<Box<dyn Path>, PathError> {
+        // The server would turn into `//server`, which Unix tools treat like `/server`
+        if self.is_unc() {
+            return Err(PathError::ConversionFailed(PathType::Unix));
         }
+
+        let unix_path = self.path.replace('\\', "/");
+        converted(UnixPath::new(unix_path))
     }
 
-    fn to_wsl(&self) -> Result<Box<dyn Path>, String> {
-        let drive_regex = Regex::new(r"^([A-Za-z]):").unwrap();
-        let wsl_path = drive_regex
+    fn to_wsl(&self) -> Result<Box<dyn Path>, PathError> {
+        if self.is_unc() {
+            return Err(PathError::ConversionFailed(PathType::Wsl));
+        }
+
+        let wsl_path = WINDOWS_DRIVE_REGEX
             .replace(&self.path, |captures: &regex::Captures| {
                 format!("/mnt/{}", &captures[1].to_lowercase())
             })
             .replace("\\", "/");
-        match WslPath::new(wsl_path) {
-            Ok(path) => Ok(Box::new(path)),
-            Err(e) => Err(e),
+        converted(WslPath::new(wsl_path))
+    }
+
+    fn to_cygwin(&self) -> Result<Box<dyn Path>, PathError> {
+        if self.is_unc() {
+            return Err(PathError::ConversionFailed(PathType::Cygwin));
         }
+
+        let cygwin_path = WINDOWS_DRIVE_REGEX
+            .replace(&self.path, |captures: &regex::Captures| {
+                format!("/cygdrive/{}", &captures[1].to_lowercase())
+            })
+            .replace('\\', "/");
+        converted(CygwinPath::new(cygwin_path))
+    }
+
+    fn to_msys(&self) -> Result<Box<dyn Path>, PathError> {
+        if self.is_unc() {
+            return Err(PathError::ConversionFailed(PathType::Msys));
+        }
+
+        let msys_path = WINDOWS_DRIVE_REGEX
+            .replace(&self.path, |captures: &regex::Captures| {
+                format!("/{}", &captures[1].to_lowercase())
+            })
+            .replace('\\', "/");
+        converted(MsysPath::new(msys_path))
     }
 
     fn as_string(&self) -> String {
-        self.path.clone()
+        match (self.long_path, self.path.strip_prefix(r"\\")) {
+            (false, _) => self.path.clone(),
+            (true, Some(unc_path)) => format!(r"\\?\UNC\{unc_path}"),
+            (true, None) => format!(r"\\?\{}", self.path),
+        }
     }
 
     fn get_type(&self) -> PathType {
@@ -123,24 +336,42 @@ impl Path for WindowsPath {
 }
 
 impl Path for UnixPath {
-    fn to_windows(&self) -> Result<Box<dyn Path>, String> {
+    fn to_windows(&self) -> Result<Box<dyn Path>, PathError> {
         let windows_path = self.path.replace('/', "\\");
-        match WindowsPath::new(windows_path) {
-            Ok(path) => Ok(Box::new(path)),
-            Err(e) => Err(e),
-        }
+        converted(WindowsPath::new(windows_path))
     }
 
-    fn to_unix(&self) -> Result<Box<dyn Path>, String> {
+    fn to_unix(&self) -> Result<Box<dyn Path>, PathError> {
         Ok(Box::new(self.clone()))
     }
 
-    fn to_wsl(&self) -> Result<Box<dyn Path>, String> {
-        let wsl_path = self.path.clone();
-        match WslPath::new(wsl_path) {
-            Ok(path) => Ok(Box::new(path)),
-            Err(e) => Err(e),
-        }
+    fn to_wsl(&self) -> Result<Box<dyn Path>, PathError> {
+        // Paths with a drive letter like `C:/Users` are mounted under `/mnt/c/Users`. Other paths only
+        // have a WSL form if they already are one.
+        let wsl_path = UNIX_DRIVE_REGEX
+            .replace(&self.path, |captures: &regex::Captures| {
+                format!("/mnt/{}/", &captures[1].to_lowercase())
+            })
+            .into_owned();
+        converted(WslPath::new(wsl_path))
+    }
+
+    fn to_cygwin(&self) -> Result<Box<dyn Path>, PathError> {
+        let cygwin_path = UNIX_DRIVE_REGEX
+            .replace(&self.path, |captures: &regex::Captures| {
+                format!("/cygdrive/{}/", &captures[1].to_lowercase())
+            })
+            .into_owned();
+        converted(CygwinPath::new(cygwin_path))
+    }
+
+    fn to_msys(&self) -> Result<Box<dyn Path>, PathError> {
+        let msys_path = UNIX_DRIVE_REGEX
+            .replace(&self.path, |captures: &regex::Captures| {
+                format!("/{}/", &captures[1].to_lowercase())
+            })
+            .into_owned();
+        converted(MsysPath::new(msys_path))
     }
 
     fn as_string(&self) -> String {
@@ -153,31 +384,34 @@ impl Path for UnixPath {
 }
 
 impl Path for WslPath {
-    fn to_windows(&self) -> Result<Box<dyn Path>, String> {
-        let drive_regex = Regex::new(r"^/mnt/([A-Za-z])").unwrap();
-        let windows_path = drive_regex
+    fn to_windows(&self) -> Result<Box<dyn Path>, PathError> {
+        let windows_path = WSL_DRIVE_REGEX
             .replace(&self.path, |captures: &regex::Captures| {
                 format!("{}:", &captures[1].to_uppercase())
             })
             .replace('/', "\\");
-        match WindowsPath::new(windows_path) {
-            Ok(path) => Ok(Box::new(path)),
-            Err(e) => Err(e),
-        }
+        converted(WindowsPath::new(windows_path))
     }
 
-    fn to_unix(&self) -> Result<Box<dyn Path>, String> {
+    fn to_unix(&self) -> Result<Box<dyn Path>, PathError> {
         let unix_path = self.path.clone();
-        match UnixPath::new(unix_path) {
-            Ok(path) => Ok(Box::new(path)),
-            Err(e) => Err(e),
-        }
+        converted(UnixPath::new(unix_path))
     }
 
-    fn to_wsl(&self) -> Result<Box<dyn Path>, String> {
+    fn to_wsl(&self) -> Result<Box<dyn Path>, PathError> {
         Ok(Box::new(self.clone()))
     }
 
+    fn to_cygwin(&self) -> Result<Box<dyn Path>, PathError> {
+        let cygwin_path = self.path.replacen("/mnt/", "/cygdrive/", 1);
+        converted(CygwinPath::new(cygwin_path))
+    }
+
+    fn to_msys(&self) -> Result<Box<dyn Path>, PathError> {
+        let msys_path = self.path.replacen("/mnt/", "/", 1);
+        converted(MsysPath::new(msys_path))
+    }
+
     fn as_string(&self) -> String {
         self.path.clone()
     }
@@ -187,11 +421,457 @@ impl Path for WslPath {
     }
 }
 
-#[derive(Debug, PartialEq)]
+impl Path for CygwinPath {
+    fn to_windows(&self) -> Result<Box<dyn Path>, PathError> {
+        let windows_path = CYGWIN_DRIVE_REGEX
+            .replace(&self.path, |captures: &regex::Captures| {
+                format!("{}:", &captures[1].to_uppercase())
+            })
+            .replace('/', "\\");
+        converted(WindowsPath::new(windows_path))
+    }
+
+    fn to_unix(&self) -> Result<Box<dyn Path>, PathError> {
+        let unix_path = self.path.clone();
+        converted(UnixPath::new(unix_path))
+    }
+
+    fn to_wsl(&self) -> Result<Box<dyn Path>, PathError> {
+        let wsl_path = self.path.replacen("/cygdrive/", "/mnt/", 1);
+        converted(WslPath::new(wsl_path))
+    }
+
+    fn to_cygwin(&self) -> Result<Box<dyn Path>, PathError> {
+        Ok(Box::new(self.clone()))
+    }
+
+    fn to_msys(&self) -> Result<Box<dyn Path>, PathError> {
+        let msys_path = self.path.replacen("/cygdrive/", "/", 1);
+        converted(MsysPath::new(msys_path))
+    }
+
+    fn as_string(&self) -> String {
+        self.path.clone()
+    }
+
+    fn get_type(&self) -> PathType {
+        PathType::Cygwin
+    }
+}
+
+impl Path for MsysPath {
+    fn to_windows(&self) -> Result<Box<dyn Path>, PathError> {
+        let windows_path = MSYS_DRIVE_REGEX
+            .replace(&self.path, |captures: &regex::Captures| {
+                format!("{}:", &captures[1].to_uppercase())
+            })
+            .replace('/', "\\");
+        converted(WindowsPath::new(windows_path))
+    }
+
+    fn to_unix(&self) -> Result<Box<dyn Path>, PathError> {
+        let unix_path = self.path.clone();
+        converted(UnixPath::new(unix_path))
+    }
+
+    fn to_wsl(&self) -> Result<Box<dyn Path>, PathError> {
+        let wsl_path = format!("/mnt{}", self.path);
+        converted(WslPath::new(wsl_path))
+    }
+
+    fn to_cygwin(&self) -> Result<Box<dyn Path>, PathError> {
+        let cygwin_path = format!("/cygdrive{}", self.path);
+        converted(CygwinPath::new(cygwin_path))
+    }
+
+    fn to_msys(&self) -> Result<Box<dyn Path>, PathError> {
+        Ok(Box::new(self.clone()))
+    }
+
+    fn as_string(&self) -> String {
+        self.path.clone()
+    }
+
+    fn get_type(&self) -> PathType {
+        PathType::Msys
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum PathType {
     Windows,
     Unix,
     Wsl,
+    Cygwin,
+    Msys,
+}
+
+/// Classifies the given string as the first path format it matches.
+pub fn detect_path(path: &str) -> Option<Box<dyn Path>> {
+    if let Ok(windows_path) = WindowsPath::new(path.to_string()) {
+        Some(Box::new(windows_path))
+    } else if let Ok(cygwin_path) = CygwinPath::new(path.to_string()) {
+        // Cygwin paths are valid Unix paths as well, so they have to be checked first
+        Some(Box::new(cygwin_path))
+    } else if let Ok(msys_path) = MsysPath::new(path.to_string()) {
+        // Top-level folders with a single letter are taken for drives, like Git Bash does
+        Some(Box::new(msys_path))
+    } else if let Ok(unix_path) = UnixPath::new(path.to_string()) {
+        Some(Box::new(unix_path))
+    } else if let Ok(wsl_path) = WslPath::new(path.to_string()) {
+        Some(Box::new(wsl_path))
+    } else {
+        None
+    }
+}
+
+/// Converts the path into every format it can be represented in.
+pub fn get_conversions(path: &dyn Path) -> Vec<Conversion> {
+    let conversions = [
+        (PathType::Windows, path.to_windows()),
+        (PathType::Unix, path.to_unix()),
+        (PathType::Wsl, path.to_wsl()),
+        (PathType::Cygwin, path.to_cygwin()),
+        (PathType::Msys, path.to_msys()),
+    ];
+
+    conversions
+        .into_iter()
+        .filter_map(|(path_type, conversion)| {
+            conversion.ok().map(|converted| Conversion {
+                path_type,
+                path: converted.as_string(),
+            })
+        })
+        .collect()
+}
+
+/// Why a text couldn't be converted by [`convert`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ConvertError {
+    /// The text isn't a path in any of the supported formats
+    NotAPath,
+    /// The path has no form in the target format, e.g. `/home` in WSL
+    NotConvertible,
+}
+
+impl fmt::Display for ConvertError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConvertError::NotAPath => write!(f, "The text is not a path."),
+            ConvertError::NotConvertible => {
+                write!(f, "The path can't be converted to the target format.")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConvertError {}
+
+/// Converts a path in any of the supported formats into the target format.
+pub fn convert(input: &str, target: PathType) -> Result<String, ConvertError> {
+    let path = detect_path(input).ok_or(ConvertError::NotAPath)?;
+    get_conversions(path.as_ref())
+        .into_iter()
+        .find(|x| x.path_type == target)
+        .map(|x| x.path)
+        .ok_or(ConvertError::NotConvertible)
+}
+
+/// Whether the Unix path points into the shared storage of an Android device, e.g. when used with `adb`.
+/// These paths have no meaningful Windows or WSL equivalent.
+pub fn is_android_path(path: &str) -> bool {
+    ANDROID_REGEX.is_match(path)
+}
+
+/// Splits a path into a WSL distribution's file system into the distribution name and the Linux path
+/// inside of it, e.g. `\\wsl$\Ubuntu\etc\hosts` or `\\wsl.localhost\Ubuntu\etc\hosts` into `Ubuntu`
+/// and `/etc/hosts`.
+pub fn parse_wsl_unc_path(path: &str) -> Option<(String, String)> {
+    let captures = WSL_UNC_REGEX.captures(path)?;
+    let linux_path = captures
+        .get(2)
+        .map_or("", |x| x.as_str())
+        .replace('\\', "/");
+
+    // The distribution root is the Linux root, with or without trailing backslash
+    let linux_path = if linux_path.is_empty() {
+        "/".to_string()
+    } else {
+        linux_path
+    };
+
+    Some((captures[1].to_string(), linux_path))
+}
+
+/// Builds the Windows path of an absolute Linux path inside the given WSL distribution.
+pub fn to_wsl_unc_path(distro: &str, linux_path: &str) -> Option<String> {
+    if !linux_path.starts_with('/') {
+        return None;
+    }
+
+    Some(format!(
+        r"\\wsl$\{}{}",
+        distro,
+        linux_path.replace('/', "\\")
+    ))
+}
+
+/// Describes the separator and root of a path string in any format.
+pub fn get_metadata(path: &str) -> PathMetadata {
+    let root = if UNC_ROOT_REGEX.is_match(path) {
+        RootKind::Unc
+    } else if DRIVE_PREFIX_REGEX.is_match(path) {
+        RootKind::Drive
+    } else if MOUNT_ROOT_REGEX.is_match(path) {
+        RootKind::Mount
+    } else if path.starts_with(['\\', '/']) {
+        RootKind::Separator
+    } else {
+        RootKind::None
+    };
+
+    // A bare drive like `C:` refers to the current folder on that drive
+    let absolute = match root {
+        RootKind::Drive => path.len() > 2,
+        RootKind::None => false,
+        _ => true,
+    };
+
+    PathMetadata {
+        separator: path.chars().find(|c| *c == '\\' || *c == '/'),
+        absolute,
+        root,
+    }
+}
+
+/// Returns the last component of the path, ignoring trailing separators. Roots have no basename.
+pub fn get_basename(path: &str) -> Option<&str> {
+    let trimmed = path.trim_end_matches(['\\', '/']);
+    let basename = match trimmed.rfind(['\\', '/']) {
+        Some(index) => &trimmed[index + 1..],
+        None => trimmed,
+    };
+
+    if basename.is_empty() || DRIVE_ROOT_REGEX.is_match(basename) {
+        None
+    } else {
+        Some(basename)
+    }
+}
+
+/// Collapses repeated separators and resolves `.` and `..` segments lexically, without looking at
+/// the file system, e.g. `a//b/./../c` to `a/c`. The root like `C:\`, `/` or the leading `\\` or
+/// `//` of UNC paths is kept, and `..` never goes above it. URLs and extended-length paths are
+/// returned as they are.
+pub fn normalize_path(path: &str) -> String {
+    if path.starts_with(r"\\?\")
+        || path
+            .find("//")
+            .is_some_and(|index| PROTOCOL_REGEX.is_match(&path[..index]))
+    {
+        return path.to_string();
+    }
+
+    // Backslashes are valid in Unix file names, so they are only separators in Windows paths
+    let windows = path.contains('\\') && !path.starts_with('/');
+    let (separator, is_separator): (char, fn(char) -> bool) = if windows {
+        ('\\', |c| c == '\\' || c == '/')
+    } else {
+        ('/', |c| c == '/')
+    };
+
+    let unc = path.chars().take(2).all(is_separator) && path.len() >= 2;
+    let (root, rest, min_segments) = if unc {
+        // The server and share are part of the root
+        (separator.to_string().repeat(2), &path[2..], 2)
+    } else if DRIVE_PREFIX_REGEX.is_match(path) {
+        let root = if path.len() > 2 {
+            format!("{}{}", &path[..2], separator)
+        } else {
+            path.to_string()
+        };
+        (root, path.get(3..).unwrap_or(""), 0)
+    } else if path.starts_with(is_separator) {
+        (separator.to_string(), &path[1..], 0)
+    } else {
+        (String::new(), path, 0)
+    };
+
+    let mut segments: Vec<&str> = Vec::new();
+    for segment in rest.split(is_separator) {
+        match segment {
+            "" | "." => {}
+            ".." if segments.len() > min_segments && segments.last() != Some(&"..") => {
+                segments.pop();
+            }
+            // Going above the root of an absolute path stays at the root
+            ".." if !root.is_empty() => {}
+            _ => segments.push(segment),
+        }
+    }
+
+    let mut normalized = root + &segments.join(&separator.to_string());
+    if !segments.is_empty() && path.ends_with(is_separator) {
+        normalized.push(separator);
+    }
+    if normalized.is_empty() {
+        normalized.push('.');
+    }
+    normalized
+}
+
+/// Replaces Windows environment variables like `%USERPROFILE%` with their values. Variables that
+/// aren't set are kept as they are.
+pub fn expand_env_vars(text: &str) -> String {
+    ENV_VAR_REGEX
+        .replace_all(text, |captures: &regex::Captures| {
+            env::var(&captures[1]).unwrap_or_else(|_| captures[0].to_string())
+        })
+        .into_owned()
+}
+
+// Private use characters standing in for glob wildcards, which aren't allowed in Windows paths
+const GLOB_STAR: char = '\u{E000}';
+const GLOB_QUESTION: char = '\u{E001}';
+
+/// Replaces the glob wildcards `*` and `?` with placeholders, so that patterns like `C:\logs\*.txt`
+/// are detected and converted like regular paths. `?` is kept in the `\\?\` prefix of long paths.
+pub fn mask_glob_chars(text: &str) -> String {
+    let (prefix, rest) = match text.strip_prefix(r"\\?\") {
+        Some(rest) => (r"\\?\", rest),
+        None => ("", text),
+    };
+    let masked: String = rest
+        .chars()
+        .map(|c| match c {
+            '*' => GLOB_STAR,
+            '?' => GLOB_QUESTION,
+            c => c,
+        })
+        .collect();
+    format!("{}{}", prefix, masked)
+}
+
+/// Restores the glob wildcards replaced by `mask_glob_chars`.
+pub fn unmask_glob_chars(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            GLOB_STAR => '*',
+            GLOB_QUESTION => '?',
+            c => c,
+        })
+        .collect()
+}
+
+/// Returns the `file://` URI of an absolute Windows path, e.g. `file:///C:/My%20Files` for
+/// `C:\My Files` or `file://server/share` for `\\server\share`. Relative paths have no URI.
+pub fn to_file_uri(windows_path: &str) -> Option<String> {
+    let (prefix, path) = if let Some(unc_path) = windows_path.strip_prefix(r"\\") {
+        ("file://", unc_path)
+    } else if DRIVE_PREFIX_REGEX.is_match(windows_path) {
+        ("file:///", windows_path)
+    } else {
+        return None;
+    };
+
+    let mut uri = prefix.to_string();
+    for byte in path.replace('\\', "/").bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' | b':' => {
+                uri.push(byte as char)
+            }
+            _ => uri.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    Some(uri)
+}
+
+/// Returns the native path of a `file://` URI, e.g. `C:\My Files` for `file:///C:/My%20Files`,
+/// `\\server\share` for `file://server/share` or `/home/me` for `file:///home/me`.
+pub fn from_file_uri(uri: &str) -> Option<String> {
+    let scheme = uri.get(..7)?;
+    if !scheme.eq_ignore_ascii_case("file://") {
+        return None;
+    }
+
+    let rest = &uri[7..];
+    let (host, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+    let path = percent_decode(path)?;
+
+    if !host.is_empty() && !host.eq_ignore_ascii_case("localhost") {
+        return Some(format!(r"\\{}{}", host, path.replace('/', "\\")));
+    }
+
+    match path.strip_prefix('/') {
+        Some(windows_path) if DRIVE_PREFIX_REGEX.is_match(windows_path) => {
+            Some(windows_path.replace('/', "\\"))
+        }
+        Some(_) => Some(path),
+        None => None,
+    }
+}
+
+/// Decodes `%XX` escapes. Returns `None` if the decoded bytes aren't valid UTF-8.
+fn percent_decode(text: &str) -> Option<String> {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            // `from_str_radix` accepts a sign, so the digits are checked first
+            .filter(|hex| bytes[i] == b'%' && hex.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+/// Returns the relative path from the `base` folder to `target`, both given as absolute Windows paths.
+/// This is purely lexical, so links aren't resolved. Paths on different drives have no relative path.
+pub fn get_relative_path(base: &str, target: &str) -> Option<String> {
+    let base: Vec<&str> = base.split('\\').filter(|x| !x.is_empty()).collect();
+    let target: Vec<&str> = target.split('\\').filter(|x| !x.is_empty()).collect();
+
+    let (base_root, target_root) = (base.first()?, target.first()?);
+    if !DRIVE_ROOT_REGEX.is_match(base_root) || !base_root.eq_ignore_ascii_case(target_root) {
+        return None;
+    }
+
+    let common_length = base
+        .iter()
+        .zip(&target)
+        .take_while(|(base, target)| base.eq_ignore_ascii_case(target))
+        .count();
+
+    let mut components = vec![".."; base.len() - common_length];
+    components.extend(&target[common_length..]);
+
+    if components.is_empty() {
+        Some(".".to_string())
+    } else {
+        Some(components.join("\\"))
+    }
+}
+
+/// Detects the format of the given string and converts it into every other format.
+/// Returns an empty list if the string isn't a path.
+pub fn convert_all(path: &str) -> Vec<Conversion> {
+    match detect_path(path) {
+        Some(path) => get_conversions(path.as_ref()),
+        None => Vec::new(),
+    }
 }
 
 #[cfg(test)]
@@ -225,7 +905,14 @@ mod tests {
             assert!(WindowsPath::new(path.to_string()).is_ok());
         }
 
-        let non_matching_paths = vec!["Users", "C:Users", "C:/Users/test/file.txt", "multi\nline"];
+        let non_matching_paths = vec![
+            "Users",
+            "C:Users",
+            "C:/Users/test/file.txt",
+            "multi\nline",
+            // Only the part after the slash would be a Windows path
+            r"foo/bar\baz",
+        ];
         for path in non_matching_paths {
             assert!(WindowsPath::new(path.to_string()).is_err());
         }
@@ -306,6 +993,134 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_cygwin_matching() {
+        let matching_paths = vec![
+            "/cygdrive/c/Users/test/file.txt",
+            "/cygdrive/c/Users/",
+            "/cygdrive/c/Users",
+            "/cygdrive/D/",
+            "/cygdrive/D",
+        ];
+        for path in matching_paths {
+            assert!(CygwinPath::new(path.to_string()).is_ok());
+            assert_eq!(detect_path(path).unwrap().get_type(), PathType::Cygwin);
+        }
+
+        let non_matching_paths = vec![
+            "/cygdrive/drive/Users",
+            "cygdrive/c/Users",
+            "/mnt/c/Users",
+            "multi\nline",
+        ];
+        for path in non_matching_paths {
+            assert!(CygwinPath::new(path.to_string()).is_err());
+        }
+    }
+
+    #[test]
+    fn test_cygwin_to_windows_conversion() {
+        let pairs = vec![
+            ("/cygdrive/c/Users/test/file.txt", r"C:\Users\test\file.txt"),
+            ("/cygdrive/c/Users/", r"C:\Users\"),
+            ("/cygdrive/D/", r"D:\"),
+            ("/cygdrive/D", r"D:"),
+        ];
+
+        for (input, expected) in pairs {
+            let cygwin_path = CygwinPath::new(input.to_string()).unwrap();
+            let windows_path = cygwin_path.to_windows().unwrap();
+            assert_eq!(windows_path.as_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_windows_to_cygwin_conversion() {
+        let pairs = vec![
+            (r"c:\Users\test\file.txt", "/cygdrive/c/Users/test/file.txt"),
+            (r"C:\", "/cygdrive/c/"),
+            (r"D:", "/cygdrive/d"),
+        ];
+
+        for (input, expected) in pairs {
+            let windows_path = WindowsPath::new(input.to_string()).unwrap();
+            let cygwin_path = windows_path.to_cygwin().unwrap();
+            assert_eq!(cygwin_path.as_string(), expected);
+        }
+
+        let windows_path = WindowsPath::new(r"\\server\share".to_string()).unwrap();
+        assert!(windows_path.to_cygwin().is_err());
+    }
+
+    #[test]
+    fn test_cygwin_wsl_conversion() {
+        let cygwin_path = CygwinPath::new("/cygdrive/c/Users/test".to_string()).unwrap();
+        assert_eq!(
+            cygwin_path.to_wsl().unwrap().as_string(),
+            "/mnt/c/Users/test"
+        );
+        assert_eq!(
+            cygwin_path.to_unix().unwrap().as_string(),
+            "/cygdrive/c/Users/test"
+        );
+
+        let wsl_path = WslPath::new("/mnt/c/Users/test".to_string()).unwrap();
+        assert_eq!(
+            wsl_path.to_cygwin().unwrap().as_string(),
+            "/cygdrive/c/Users/test"
+        );
+
+        let unix_path = UnixPath::new("C:/Users/test".to_string()).unwrap();
+        assert_eq!(
+            unix_path.to_cygwin().unwrap().as_string(),
+            "/cygdrive/c/Users/test"
+        );
+        let unix_path = UnixPath::new("/home/user".to_string()).unwrap();
+        assert!(unix_path.to_cygwin().is_err());
+    }
+
+    #[test]
+    fn test_msys_matching() {
+        for path in ["/c/Users/test/file.txt", "/c/Users/", "/c", "/D/"] {
+            assert!(MsysPath::new(path.to_string()).is_ok());
+            assert_eq!(detect_path(path).unwrap().get_type(), PathType::Msys);
+        }
+
+        // Genuine top-level folders stay Unix paths
+        for path in ["/etc/hosts", "/usr", "/mnt/c/Users", "c/Users", "/c//Users"] {
+            assert!(MsysPath::new(path.to_string()).is_err());
+        }
+        assert_eq!(detect_path("/etc").unwrap().get_type(), PathType::Unix);
+    }
+
+    #[test]
+    fn test_msys_conversion() {
+        let msys_path = MsysPath::new("/c/Users/test".to_string()).unwrap();
+        assert_eq!(
+            msys_path.to_windows().unwrap().as_string(),
+            r"C:\Users\test"
+        );
+        assert_eq!(msys_path.to_wsl().unwrap().as_string(), "/mnt/c/Users/test");
+        assert_eq!(
+            msys_path.to_cygwin().unwrap().as_string(),
+            "/cygdrive/c/Users/test"
+        );
+
+        let windows_path = WindowsPath::new(r"C:\Users\test".to_string()).unwrap();
+        assert_eq!(windows_path.to_msys().unwrap().as_string(), "/c/Users/test");
+        let windows_path = WindowsPath::new(r"D:\".to_string()).unwrap();
+        assert_eq!(windows_path.to_msys().unwrap().as_string(), "/d/");
+        let windows_path = WindowsPath::new(r"\\server\share".to_string()).unwrap();
+        assert!(windows_path.to_msys().is_err());
+
+        let wsl_path = WslPath::new("/mnt/c/Users/test".to_string()).unwrap();
+        assert_eq!(wsl_path.to_msys().unwrap().as_string(), "/c/Users/test");
+        let unix_path = UnixPath::new("C:/Users/test".to_string()).unwrap();
+        assert_eq!(unix_path.to_msys().unwrap().as_string(), "/c/Users/test");
+        let unix_path = UnixPath::new("/home/user".to_string()).unwrap();
+        assert!(unix_path.to_msys().is_err());
+    }
+
     #[test]
     fn test_wsl_to_windows_conversion() {
         let pairs = vec![
@@ -339,15 +1154,24 @@ mod tests {
 
     #[test]
     fn test_unix_to_wsl_conversion() {
-        // TODO: This functionality is currently not supported
+        let pairs = vec![
+            ("C:/", "/mnt/c/"),
+            ("d:/", "/mnt/d/"),
+            ("d:/foo", "/mnt/d/foo"),
+            ("C:/Users/test/file.txt", "/mnt/c/Users/test/file.txt"),
+            ("/mnt/c/Users", "/mnt/c/Users"),
+        ];
 
-        // let pairs = vec![("C:/", "/mnt/c/"), ("d:/", "/mnt/d/")];
+        for (input, expected) in pairs {
+            let unix_path = UnixPath::new(input.to_string()).unwrap();
+            let wsl_path = unix_path.to_wsl().unwrap();
+            assert_eq!(wsl_path.as_string(), expected);
+        }
 
-        // for (input, expected) in pairs {
-        //     let unix_path = UnixPath::new(input.to_string()).unwrap();
-        //     let wsl_path = unix_path.to_wsl().unwrap();
-        //     assert_eq!(wsl_path.as_string(), expected);
-        // }
+        for input in ["/home/user", "relative/path", "C:folder/file"] {
+            let unix_path = UnixPath::new(input.to_string()).unwrap();
+            assert!(unix_path.to_wsl().is_err());
+        }
     }
 
     #[test]
@@ -365,4 +1189,474 @@ mod tests {
             assert_eq!(unix_path.as_string(), expected);
         }
     }
+
+    #[test]
+    fn test_convert_all() {
+        let conversions = convert_all(r"C:\Users\test");
+        let pairs: Vec<(PathType, &str)> = conversions
+            .iter()
+            .map(|x| (x.path_type, x.path.as_str()))
+            .collect();
+        assert_eq!(
+            pairs,
+            vec![
+                (PathType::Windows, r"C:\Users\test"),
+                (PathType::Unix, "C:/Users/test"),
+                (PathType::Wsl, "/mnt/c/Users/test"),
+                (PathType::Cygwin, "/cygdrive/c/Users/test"),
+                (PathType::Msys, "/c/Users/test"),
+            ]
+        );
+
+        assert!(convert_all("no path").is_empty());
+    }
+
+    #[test]
+    fn test_android_matching() {
+        let matching_paths = vec![
+            "/sdcard/DCIM/photo.jpg",
+            "/sdcard",
+            "/storage/emulated/0/Download/",
+        ];
+        for path in matching_paths {
+            assert!(is_android_path(path));
+        }
+
+        let non_matching_paths = vec![
+            "/sdcard2/file",
+            "sdcard/file",
+            "/storage/emulated/1",
+            "/home/sdcard",
+        ];
+        for path in non_matching_paths {
+            assert!(!is_android_path(path));
+        }
+    }
+
+    #[test]
+    fn test_wsl_unc_conversion() {
+        let pairs = vec![
+            (r"\\wsl$\Ubuntu\", "/"),
+            (r"\\wsl$\Ubuntu\etc\hosts", "/etc/hosts"),
+            (r"\\wsl$\Ubuntu\home\user\", "/home/user/"),
+        ];
+
+        for (input, expected) in pairs {
+            let (distro, linux_path) = parse_wsl_unc_path(input).unwrap();
+            assert_eq!(distro, "Ubuntu");
+            assert_eq!(linux_path, expected);
+            assert_eq!(to_wsl_unc_path(&distro, &linux_path).unwrap(), input);
+        }
+
+        // The bare distribution root without trailing backslash is the Linux root as well
+        let (_, linux_path) = parse_wsl_unc_path(r"\\wsl$\Ubuntu").unwrap();
+        assert_eq!(linux_path, "/");
+
+        // Newer Windows versions use `wsl.localhost` instead of `wsl$`
+        let (distro, linux_path) =
+            parse_wsl_unc_path(r"\\wsl.localhost\Debian\home\me\file").unwrap();
+        assert_eq!(distro, "Debian");
+        assert_eq!(linux_path, "/home/me/file");
+        let (distro, linux_path) = parse_wsl_unc_path(r"\\WSL$\Ubuntu-22.04\etc").unwrap();
+        assert_eq!(distro, "Ubuntu-22.04");
+        assert_eq!(linux_path, "/etc");
+
+        assert!(parse_wsl_unc_path(r"\\wsl$\").is_none());
+        assert!(parse_wsl_unc_path(r"\\wsl.localhost\").is_none());
+        assert!(parse_wsl_unc_path(r"\\server\share\etc").is_none());
+        assert!(to_wsl_unc_path("Ubuntu", "etc/hosts").is_none());
+    }
+
+    #[test]
+    fn test_relative_path() {
+        let cases = vec![
+            (
+                r"C:\projects\app",
+                r"C:\projects\app\src\main.rs",
+                Some(r"src\main.rs"),
+            ),
+            (r"C:\projects\app\", r"C:\projects\lib", Some(r"..\lib")),
+            (r"C:\projects\app", r"c:\Projects\App", Some(".")),
+            (r"C:\projects\app\src", r"C:\", Some(r"..\..\..")),
+            (r"C:\projects", r"D:\projects", None),
+            (r"\projects", r"\projects\app", None),
+        ];
+
+        for (base, target, expected) in cases {
+            assert_eq!(get_relative_path(base, target).as_deref(), expected);
+        }
+    }
+
+    #[test]
+    fn test_basename() {
+        let pairs = vec![
+            (r"C:\foo\file.txt", Some("file.txt")),
+            (r"C:\foo\", Some("foo")),
+            ("/home/user/", Some("user")),
+            ("file.txt", Some("file.txt")),
+            (r"C:\", None),
+            ("C:", None),
+            ("/", None),
+        ];
+
+        for (input, expected) in pairs {
+            assert_eq!(get_basename(input), expected);
+        }
+    }
+
+    #[test]
+    fn test_unicode_conversion() {
+        // The character classes only exclude specific ASCII characters, so any other code point is allowed
+        let windows_path = WindowsPath::new(r"C:\📁Projects\©file.txt".to_string()).unwrap();
+        assert_eq!(
+            windows_path.to_unix().unwrap().as_string(),
+            "C:/📁Projects/©file.txt"
+        );
+        assert_eq!(
+            windows_path.to_wsl().unwrap().as_string(),
+            "/mnt/c/📁Projects/©file.txt"
+        );
+
+        let wsl_path = WslPath::new("/mnt/c/📁Projects/©file.txt".to_string()).unwrap();
+        assert_eq!(
+            wsl_path.to_windows().unwrap().as_string(),
+            r"C:\📁Projects\©file.txt"
+        );
+
+        let unix_path = UnixPath::new("/home/📁Projects/©file.txt".to_string()).unwrap();
+        assert_eq!(
+            unix_path.to_windows().unwrap().as_string(),
+            r"\home\📁Projects\©file.txt"
+        );
+    }
+
+    #[test]
+    fn test_dot_relative_paths() {
+        let pairs = vec![
+            (r".\foo\bar", "./foo/bar"),
+            (r".\foo", "./foo"),
+            (r".\", "./"),
+            (r"..\foo\bar.txt", "../foo/bar.txt"),
+            (r".\.config\app", "./.config/app"),
+        ];
+
+        for (windows, unix) in pairs {
+            let windows_path = detect_path(windows).unwrap();
+            assert_eq!(windows_path.get_type(), PathType::Windows);
+            assert_eq!(windows_path.to_unix().unwrap().as_string(), unix);
+
+            let unix_path = detect_path(unix).unwrap();
+            assert_eq!(unix_path.get_type(), PathType::Unix);
+            assert_eq!(unix_path.to_windows().unwrap().as_string(), windows);
+
+            // Relative paths have no drive, so there is no WSL form
+            assert!(unix_path.to_wsl().is_err());
+        }
+    }
+
+    #[test]
+    fn test_bare_names_are_not_paths() {
+        for name in ["file.txt", "file", ".hidden"] {
+            assert!(detect_path(name).is_none(), "{name}");
+        }
+    }
+
+    #[test]
+    fn test_metadata() {
+        let cases = vec![
+            (r"C:\Users", Some('\\'), true, RootKind::Drive),
+            ("C:/Users", Some('/'), true, RootKind::Drive),
+            ("C:", None, false, RootKind::Drive),
+            (r"\\server\share", Some('\\'), true, RootKind::Unc),
+            ("//server/share", Some('/'), true, RootKind::Unc),
+            ("/mnt/c/Users", Some('/'), true, RootKind::Mount),
+            ("/mnt/data", Some('/'), true, RootKind::Separator),
+            (r"\Users", Some('\\'), true, RootKind::Separator),
+            ("./foo/bar", Some('/'), false, RootKind::None),
+            ("file.txt", None, false, RootKind::None),
+        ];
+
+        for (path, separator, absolute, root) in cases {
+            let expected = PathMetadata {
+                separator,
+                absolute,
+                root,
+            };
+            assert_eq!(get_metadata(path), expected, "{path}");
+        }
+
+        let wsl_path = WslPath::new("/mnt/c/Users".to_string()).unwrap();
+        assert_eq!(wsl_path.get_metadata().root, RootKind::Mount);
+    }
+
+    #[test]
+    fn test_unc_paths() {
+        for input in [r"\\server\share", r"\\server\share\", r"\\server"] {
+            let windows_path = WindowsPath::new(input.to_string()).unwrap();
+            assert!(windows_path.is_unc());
+            assert_eq!(windows_path.to_windows().unwrap().as_string(), input);
+            assert_eq!(
+                windows_path.to_unix().err(),
+                Some(PathError::ConversionFailed(PathType::Unix))
+            );
+            assert_eq!(
+                windows_path.to_wsl().err(),
+                Some(PathError::ConversionFailed(PathType::Wsl))
+            );
+        }
+
+        let windows_path = WindowsPath::new(r"\Users\test".to_string()).unwrap();
+        assert!(!windows_path.is_unc());
+
+        // Only the Windows form is offered, without half-converted paths like `//server/share`
+        for input in [r"\\server\share\dir", r"\\?\UNC\server\share\dir"] {
+            let conversions = convert_all(input);
+            let types: Vec<PathType> = conversions.iter().map(|x| x.path_type).collect();
+            assert_eq!(types, vec![PathType::Windows], "{input}");
+            assert_eq!(
+                convert(input, PathType::Unix),
+                Err(ConvertError::NotConvertible)
+            );
+        }
+    }
+
+    #[test]
+    fn test_repeated_wsl_conversion() {
+        // The drive regexes are compiled once and shared by all conversions
+        let mut path: Box<dyn Path> =
+            Box::new(WindowsPath::new(r"C:\Users\test".to_string()).unwrap());
+        for _ in 0..1000 {
+            path = path.to_wsl().unwrap();
+            assert_eq!(path.as_string(), "/mnt/c/Users/test");
+            path = path.to_windows().unwrap();
+            assert_eq!(path.as_string(), r"C:\Users\test");
+        }
+    }
+
+    #[test]
+    fn test_spaces_and_parentheses() {
+        let windows_path = r"C:\Program Files (x86)\App";
+        let wsl_path = "/mnt/c/Program Files (x86)/App";
+        let unix_path = "C:/Program Files (x86)/App";
+        assert_eq!(
+            detect_path(windows_path).unwrap().get_type(),
+            PathType::Windows
+        );
+
+        let path = WindowsPath::new(windows_path.to_string()).unwrap();
+        assert_eq!(path.to_wsl().unwrap().as_string(), wsl_path);
+        assert_eq!(path.to_unix().unwrap().as_string(), unix_path);
+
+        let path = WslPath::new(wsl_path.to_string()).unwrap();
+        assert_eq!(path.to_windows().unwrap().as_string(), windows_path);
+        let path = UnixPath::new(unix_path.to_string()).unwrap();
+        assert_eq!(path.to_windows().unwrap().as_string(), windows_path);
+
+        // Short names of folders with spaces and parentheses
+        let path = WindowsPath::new(r"C:\PROGRA~2\Foo~1".to_string()).unwrap();
+        assert_eq!(path.to_wsl().unwrap().as_string(), "/mnt/c/PROGRA~2/Foo~1");
+        let path = WindowsPath::new(r"C:\Program Files (x86)\Foo~1".to_string()).unwrap();
+        assert_eq!(
+            path.to_wsl().unwrap().as_string(),
+            "/mnt/c/Program Files (x86)/Foo~1"
+        );
+    }
+
+    #[test]
+    fn test_file_uri() {
+        let pairs = vec![
+            (r"C:\Users\test\file.txt", "file:///C:/Users/test/file.txt"),
+            (r"C:\My Files\100%.txt", "file:///C:/My%20Files/100%25.txt"),
+            (r"D:\", "file:///D:/"),
+            (r"\\server\share\x y", "file://server/share/x%20y"),
+            ("C:\\Gr\u{fc}\u{df}e", "file:///C:/Gr%C3%BC%C3%9Fe"),
+        ];
+        for (input, expected) in pairs {
+            assert_eq!(to_file_uri(input).as_deref(), Some(expected), "{input}");
+        }
+
+        assert_eq!(to_file_uri(r"Users\test"), None);
+        assert_eq!(to_file_uri(r"\Users\test"), None);
+    }
+
+    #[test]
+    fn test_from_file_uri() {
+        let pairs = vec![
+            ("file:///C:/Users/test/file.txt", r"C:\Users\test\file.txt"),
+            ("file:///C:/My%20Files/100%25.txt", r"C:\My Files\100%.txt"),
+            ("FILE:///d:/", r"d:\"),
+            ("file://localhost/C:/x", r"C:\x"),
+            ("file://server/share/x%20y", r"\\server\share\x y"),
+            ("file:///C:/Gr%C3%BC%C3%9Fe", "C:\\Gr\u{fc}\u{df}e"),
+            ("file:///home/me/a%2", "/home/me/a%2"),
+            ("file:///C:/a%+Fb", r"C:\a%+Fb"),
+        ];
+        for (input, expected) in pairs {
+            assert_eq!(from_file_uri(input).as_deref(), Some(expected), "{input}");
+        }
+
+        // Encoding a path and decoding it again gives the same path
+        for path in [r"C:\My Files\100%.txt", r"\\server\share\#1"] {
+            assert_eq!(
+                from_file_uri(&to_file_uri(path).unwrap()).as_deref(),
+                Some(path)
+            );
+        }
+
+        assert_eq!(from_file_uri("file://"), None);
+        assert_eq!(from_file_uri("https://example.com/a"), None);
+        assert_eq!(from_file_uri("file:///C:/%FF"), None);
+    }
+
+    #[test]
+    fn test_expand_env_vars() {
+        env::set_var("PATHTE_TEST_PROFILE", r"C:\Users\me");
+        assert_eq!(
+            expand_env_vars(r"%PATHTE_TEST_PROFILE%\Documents"),
+            r"C:\Users\me\Documents"
+        );
+        assert_eq!(
+            expand_env_vars("%PATHTE_TEST_PROFILE%%PATHTE_TEST_PROFILE%"),
+            r"C:\Users\meC:\Users\me"
+        );
+
+        assert_eq!(
+            expand_env_vars(r"%PATHTE_TEST_UNSET%\Documents"),
+            r"%PATHTE_TEST_UNSET%\Documents"
+        );
+        assert_eq!(expand_env_vars(r"C:\100%\a"), r"C:\100%\a");
+    }
+
+    #[test]
+    fn test_glob_chars() {
+        for input in [
+            r"C:\logs\*.txt",
+            "/var/log/**/*.log",
+            r"C:\a?c\[ab].txt",
+            "/mnt/c/*",
+        ] {
+            let masked = mask_glob_chars(input);
+            assert!(!masked.contains(['*', '?']));
+            assert_eq!(unmask_glob_chars(&masked), input);
+        }
+        assert!(WindowsPath::is_windows_path(&mask_glob_chars(
+            r"C:\logs\*.txt"
+        )));
+        assert_eq!(mask_glob_chars(r"\\?\C:\a?"), "\\\\?\\C:\\a\u{E001}");
+    }
+
+    #[test]
+    fn test_long_path_prefix() {
+        let windows_path = WindowsPath::new(r"\\?\C:\very\long\path".to_string()).unwrap();
+        assert_eq!(windows_path.as_string(), r"\\?\C:\very\long\path");
+        assert_eq!(
+            windows_path.to_windows().unwrap().as_string(),
+            r"\\?\C:\very\long\path"
+        );
+        assert_eq!(
+            windows_path.to_unix().unwrap().as_string(),
+            "C:/very/long/path"
+        );
+        assert_eq!(
+            windows_path.to_wsl().unwrap().as_string(),
+            "/mnt/c/very/long/path"
+        );
+        assert_eq!(
+            detect_path(r"\\?\C:\very").unwrap().get_type(),
+            PathType::Windows
+        );
+
+        let windows_path = WindowsPath::new(r"\\?\UNC\server\share\a".to_string()).unwrap();
+        assert!(windows_path.is_unc());
+        assert_eq!(windows_path.as_string(), r"\\?\UNC\server\share\a");
+        assert!(windows_path.to_unix().is_err());
+        assert!(windows_path.to_wsl().is_err());
+    }
+
+    #[test]
+    fn test_convert() {
+        assert_eq!(
+            convert(r"C:\Users\test", PathType::Wsl),
+            Ok("/mnt/c/Users/test".to_string())
+        );
+        assert_eq!(
+            convert("/c/Users", PathType::Windows),
+            Ok(r"C:\Users".to_string())
+        );
+        assert_eq!(
+            convert("Users", PathType::Unix),
+            Err(ConvertError::NotAPath)
+        );
+        assert_eq!(
+            convert("/home/user", PathType::Wsl),
+            Err(ConvertError::NotConvertible)
+        );
+    }
+
+    #[test]
+    fn test_path_errors() {
+        assert_eq!(
+            WindowsPath::new(String::new()).err(),
+            Some(PathError::EmptyInput)
+        );
+        assert_eq!(
+            UnixPath::new(r"C:\Users".to_string()).err(),
+            Some(PathError::NotRecognized(PathType::Unix))
+        );
+        assert_eq!(
+            WslPath::new("/home".to_string()).err(),
+            Some(PathError::NotRecognized(PathType::Wsl))
+        );
+
+        let unc_path = WindowsPath::new(r"\\server\share".to_string()).unwrap();
+        assert_eq!(
+            unc_path.to_wsl().err(),
+            Some(PathError::ConversionFailed(PathType::Wsl))
+        );
+        let unix_path = UnixPath::new("/home/user".to_string()).unwrap();
+        assert_eq!(
+            unix_path.to_cygwin().err(),
+            Some(PathError::ConversionFailed(PathType::Cygwin))
+        );
+    }
+
+    #[test]
+    fn test_normalize_path() {
+        let pairs = vec![
+            // Repeated separators
+            ("/home//user///a.txt", "/home/user/a.txt"),
+            (r"C:\\Users\\test", r"C:\Users\test"),
+            // `.` segments
+            ("./bar", "bar"),
+            ("/home/./user/.", "/home/user"),
+            (r"C:\.\Users", r"C:\Users"),
+            // `..` segments
+            ("a/b/../c", "a/c"),
+            (r"C:\Users\test\..\..\Windows", r"C:\Windows"),
+            ("../../a/../b", "../../b"),
+            (".", "."),
+            ("a/..", "."),
+            // `..` doesn't go above the root
+            ("/../etc", "/etc"),
+            (r"C:\..\Windows", r"C:\Windows"),
+            (r"\\server\share\..\..\dir", r"\\server\share\dir"),
+            // Roots and trailing separators are kept
+            ("/", "/"),
+            (r"C:\", r"C:\"),
+            ("C:", "C:"),
+            ("C:/Users//test/", "C:/Users/test/"),
+            (r"C:\Users\\", r"C:\Users\"),
+            ("//server//share/a", "//server/share/a"),
+            // Mixed separators in Windows paths
+            (r"C:\Users/test\\a", r"C:\Users\test\a"),
+            // Backslashes in Unix paths are part of file names
+            (r"/home/a\\b/../c", r"/home/c"),
+            // URLs and extended-length paths are kept as they are
+            ("https://example.com//a/../b", "https://example.com//a/../b"),
+            (r"\\?\C:\a\..\b", r"\\?\C:\a\..\b"),
+        ];
+        for (input, expected) in pairs {
+            assert_eq!(normalize_path(input), expected, "{input}");
+        }
+    }
 }
@@ -1,62 +1,911 @@
-use crate::path::{Path, PathType, UnixPath, WindowsPath, WslPath};
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::de::{value, IntoDeserializer};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::str::FromStr;
 
+use crate::config::{
+    self, CommitUnchangedBehavior, Config, ConversionRule, MultiLineMode, VariableStyle,
+};
+use crate::path::{self, Path, PathMetadata, PathType, WindowsPath};
+use crate::system;
+
+lazy_static! {
+    // Matches a trailing `:line` or `:line:column` as printed by compilers
+    static ref LOCATION_SUFFIX_REGEX: Regex = Regex::new(r"^(.+?)((?::\d+){1,2})$").unwrap();
+    static ref DRIVE_LETTER_REGEX: Regex = Regex::new(r"^[A-Za-z]$").unwrap();
+    static ref UNIX_DRIVE_PATH_REGEX: Regex = Regex::new(r"^[A-Za-z]:/").unwrap();
+    static ref DRIVE_PATH_REGEX: Regex = Regex::new(r"^([A-Za-z]):(\\.*)?$").unwrap();
+    // File headers of unified diffs like `+++ b/src/main.rs`, optionally followed by a tab and timestamp
+    static ref DIFF_PREFIX_REGEX: Regex =
+        Regex::new(r"^(?:(?:---|\+\+\+) (?:[ab]/)?|[ab]/)([^\t]+)(?:\t.*)?$").unwrap();
+    static ref FORWARD_UNC_REGEX: Regex = Regex::new(r"^//[^/]+/[^/]").unwrap();
+    // Device namespace paths like `\\.\C:` or `\\.\PhysicalDrive0`
+    static ref DEVICE_PATH_REGEX: Regex = Regex::new(r"^\\\\\.\\").unwrap();
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OptionKind {
+    Windows,
+    Unix,
+    Wsl,
+    WindowsShort,
+    Unc,
+    UncUnix,
+    MappedDrive,
+    Remapped,
+    RemappedUnix,
+    RemappedWsl,
+    WslDistro,
+    WslUnc,
+    WindowsCanonical,
+    Relative,
+    RelativeUnix,
+    Basename,
+    MakeUnix,
+    MakeWsl,
+    HomeVariable,
+    Cygwin,
+    Msys,
+    Osc8Link,
+    FileUri,
+    Rule,
+}
+
+/// Characters surrounding a copied path that are re-applied to the converted path.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Wrapper {
+    Backticks,
+    AngleBrackets,
+    /// As added by "Copy as path" in Explorer and by many terminals
+    DoubleQuotes,
+    SingleQuotes,
+}
+
+/// A line of multi-line text, split into the path it starts with and the surrounding text.
+struct PathLine<'a> {
+    leading: &'a str,
+    token: &'a str,
+    trailing: &'a str,
+    path: Option<Box<dyn Path>>,
+}
+
+/// Text around a copied path that is not converted but re-applied to the converted path.
+#[derive(Clone, Default)]
+struct Decoration {
+    wrapper: Option<Wrapper>,
+    suffix: String,
+}
+
+#[derive(Clone)]
+struct PathOption {
+    kind: OptionKind,
+    path: String,
+    /// Whether converting the option back yields exactly the copied path
+    lossless: bool,
+    /// Whether the path exists on disk, if it could be checked
+    exists: Option<bool>,
+}
+
+/// Configures which options a [`PathSelection`] offers and in which order.
+#[derive(Clone)]
+pub struct PathSelectionBuilder {
+    enabled_options: Vec<OptionKind>,
+    option_priority: Vec<OptionKind>,
+    initial_option: Option<OptionKind>,
+    last_option: Option<OptionKind>,
+    deduplicate: bool,
+    max_options: Option<usize>,
+    max_input_length: Option<usize>,
+    multi_line_mode: MultiLineMode,
+    android_paths: bool,
+    drive_remap: Vec<(char, char)>,
+    strip_diff_prefixes: bool,
+    relative_base: Option<String>,
+    basename: bool,
+    prefer_wsl_for_drive_paths: bool,
+    makefile_escaping: bool,
+    home_variable: Option<(VariableStyle, String)>,
+    wsl_distro: Option<String>,
+    show_single_option: bool,
+    cygwin: bool,
+    msys: bool,
+    osc8_link: bool,
+    file_uri: bool,
+    expand_env_vars: bool,
+    normalize_paths: bool,
+    glob_patterns: bool,
+    rules: Vec<ConversionRule>,
+    output_template: String,
+    commit_unchanged_behavior: CommitUnchangedBehavior,
+    start_at_last: bool,
+    quote_spaces: bool,
+}
+
+#[derive(Clone)]
 pub struct PathSelection {
-    options: Vec<Box<dyn Path>>,
+    options: Vec<PathOption>,
     current: usize,
+    decoration: Decoration,
+    original: String,
+    cycled: bool,
+    /// The option in the format of the copied path
+    source_kind: OptionKind,
+    output_template: String,
+    quote_spaces: bool,
+    commit_unchanged_behavior: CommitUnchangedBehavior,
 }
 
+/// An option as it is shown by the overlay and dialogs.
 #[derive(Clone)]
 pub struct PathSelectionInfoEntry {
     pub label: String,
     pub path: String,
+    /// Label of the format the copied path was detected as
+    pub source_label: String,
+    pub lossless: bool,
+    pub exists: Option<bool>,
+    pub metadata: PathMetadata,
 }
 
 #[derive(Clone)]
 pub struct PathSelectionInfo {
     pub options: Vec<PathSelectionInfoEntry>,
     pub selected: usize,
+    /// The exact text that is pasted when the selection is committed
+    pub output: String,
+    /// Whether the selected option has just been pasted
+    pub committed: bool,
 }
 
-unsafe impl Send for PathSelection {}
+impl OptionKind {
+    pub const ALL: [OptionKind; 24] = [
+        OptionKind::Windows,
+        OptionKind::Unix,
+        OptionKind::Wsl,
+        OptionKind::WindowsShort,
+        OptionKind::Unc,
+        OptionKind::UncUnix,
+        OptionKind::MappedDrive,
+        OptionKind::Remapped,
+        OptionKind::RemappedUnix,
+        OptionKind::RemappedWsl,
+        OptionKind::WslDistro,
+        OptionKind::WslUnc,
+        OptionKind::WindowsCanonical,
+        OptionKind::Relative,
+        OptionKind::RelativeUnix,
+        OptionKind::Basename,
+        OptionKind::MakeUnix,
+        OptionKind::MakeWsl,
+        OptionKind::HomeVariable,
+        OptionKind::Cygwin,
+        OptionKind::Msys,
+        OptionKind::Osc8Link,
+        OptionKind::FileUri,
+        OptionKind::Rule,
+    ];
 
-impl PathSelection {
-    pub fn new(raw_path: String) -> Option<Self> {
-        let path = PathSelection::get_initial_path(raw_path)?;
+    pub fn label(&self) -> &'static str {
+        match self {
+            OptionKind::Windows => "Win",
+            OptionKind::Unix => "Unix",
+            OptionKind::Wsl => "WSL",
+            OptionKind::WindowsShort => "Win (8.3)",
+            OptionKind::Unc => "UNC",
+            OptionKind::UncUnix => "UNC/",
+            OptionKind::MappedDrive => "Drive",
+            OptionKind::Remapped => "Remap",
+            OptionKind::RemappedUnix => "Remap/",
+            OptionKind::RemappedWsl => "Remap WSL",
+            OptionKind::WslDistro => "Distro",
+            OptionKind::WslUnc => "Distro UNC",
+            OptionKind::WindowsCanonical => "Win (on disk)",
+            OptionKind::Relative => "Rel",
+            OptionKind::RelativeUnix => "Rel/",
+            OptionKind::Basename => "Name",
+            OptionKind::MakeUnix => "Make",
+            OptionKind::MakeWsl => "Make WSL",
+            OptionKind::HomeVariable => "Var",
+            OptionKind::Cygwin => "Cyg",
+            OptionKind::Msys => "MSYS",
+            OptionKind::Osc8Link => "Link",
+            OptionKind::FileUri => "URI",
+            OptionKind::Rule => "Rule",
+        }
+    }
 
-        let options = vec![path.to_windows(), path.to_unix(), path.to_wsl()];
-        let ok_options: Vec<Box<dyn Path>> = options
-            .into_iter()
-            .filter(|x| x.is_ok())
-            .flatten()
-            .collect();
+    /// Quotes the path for the shells that use its format, i.e. cmd and PowerShell for Windows paths
+    /// and POSIX shells for Unix paths. Returns `None` for formats that are already escaped or would
+    /// break if quoted, like paths with a home variable.
+    fn quote(&self, path: &str) -> Option<String> {
+        match self {
+            OptionKind::Windows
+            | OptionKind::WindowsShort
+            | OptionKind::Unc
+            | OptionKind::MappedDrive
+            | OptionKind::Remapped
+            | OptionKind::WslUnc
+            | OptionKind::WindowsCanonical
+            | OptionKind::Relative => Some(format!("\"{path}\"")),
+            OptionKind::Unix
+            | OptionKind::Wsl
+            | OptionKind::UncUnix
+            | OptionKind::RemappedUnix
+            | OptionKind::RemappedWsl
+            | OptionKind::WslDistro
+            | OptionKind::RelativeUnix
+            | OptionKind::Cygwin
+            | OptionKind::Msys => Some(format!("'{}'", path.replace('\'', r"'\''"))),
+            OptionKind::Basename
+            | OptionKind::MakeUnix
+            | OptionKind::MakeWsl
+            | OptionKind::HomeVariable
+            | OptionKind::Osc8Link
+            | OptionKind::FileUri
+            | OptionKind::Rule => None,
+        }
+    }
+}
 
-        if ok_options.len() == 1 {
-            // If there is only one option, there is nothing to select
+impl Wrapper {
+    const ALL: [Wrapper; 4] = [
+        Wrapper::Backticks,
+        Wrapper::AngleBrackets,
+        Wrapper::DoubleQuotes,
+        Wrapper::SingleQuotes,
+    ];
+
+    fn delimiters(&self) -> (char, char) {
+        match self {
+            Wrapper::Backticks => ('`', '`'),
+            Wrapper::AngleBrackets => ('<', '>'),
+            Wrapper::DoubleQuotes => ('"', '"'),
+            Wrapper::SingleQuotes => ('\'', '\''),
+        }
+    }
+
+    fn strip<'a>(&self, text: &'a str) -> Option<&'a str> {
+        let (start, end) = self.delimiters();
+        text.strip_prefix(start)?.strip_suffix(end)
+    }
+
+    fn apply(&self, text: &str) -> String {
+        let (start, end) = self.delimiters();
+        format!("{}{}{}", start, text, end)
+    }
+}
+
+impl<'a> PathLine<'a> {
+    fn new(line: &'a str) -> Self {
+        let token_start = line.len() - line.trim_start().len();
+        let token_end = line[token_start..]
+            .find(char::is_whitespace)
+            .map_or(line.len(), |end| token_start + end);
+        let token = &line[token_start..token_end];
+
+        PathLine {
+            leading: &line[..token_start],
+            token,
+            trailing: &line[token_end..],
+            path: path::detect_path(token),
+        }
+    }
+
+    /// Like `new`, but the whole line apart from surrounding whitespace is the path, so it may
+    /// contain spaces.
+    fn whole(line: &'a str) -> Self {
+        let token = line.trim();
+        let token_start = line.len() - line.trim_start().len();
+        let token_end = token_start + token.len();
+
+        PathLine {
+            leading: &line[..token_start],
+            token,
+            trailing: &line[token_end..],
+            path: path::detect_path(token),
+        }
+    }
+
+    /// Returns the line with its path converted, or the unchanged line if it has no path.
+    fn convert(&self, path_type: PathType) -> Option<String> {
+        let converted = match &self.path {
+            Some(path) => {
+                path::get_conversions(path.as_ref())
+                    .into_iter()
+                    .find(|x| x.path_type == path_type)?
+                    .path
+            }
+            None => self.token.to_string(),
+        };
+
+        Some(format!("{}{}{}", self.leading, converted, self.trailing))
+    }
+}
+
+impl PathOption {
+    fn new(kind: OptionKind, path: String) -> Self {
+        PathOption {
+            kind,
+            path,
+            lossless: false,
+            exists: None,
+        }
+    }
+
+    fn is_lossless_for(&self, source: &dyn Path) -> bool {
+        path::detect_path(&self.path)
+            .map(|path| path::get_conversions(path.as_ref()))
+            .is_some_and(|conversions| {
+                conversions
+                    .iter()
+                    .any(|x| x.path_type == source.get_type() && x.path == source.as_string())
+            })
+    }
+
+    fn check_exists(&self) -> Option<bool> {
+        match self.kind {
+            // UNC paths are not checked since unreachable servers can take seconds to time out
+            OptionKind::Windows | OptionKind::WindowsShort => {
+                Some(std::path::Path::new(&self.path).exists())
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Decoration {
+    fn apply(&self, path: &str) -> String {
+        let path = format!("{}{}", path, self.suffix);
+        match self.wrapper {
+            Some(wrapper) => wrapper.apply(&path),
+            None => path,
+        }
+    }
+}
+
+impl FromStr for OptionKind {
+    type Err = String;
+
+    /// Parses the name that is used in the config, e.g. `wsl` or `windows_short`.
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        OptionKind::deserialize(name.into_deserializer()).map_err(|e: value::Error| e.to_string())
+    }
+}
+
+impl From<PathType> for OptionKind {
+    fn from(path_type: PathType) -> Self {
+        match path_type {
+            PathType::Windows => OptionKind::Windows,
+            PathType::Unix => OptionKind::Unix,
+            PathType::Wsl => OptionKind::Wsl,
+            PathType::Cygwin => OptionKind::Cygwin,
+            PathType::Msys => OptionKind::Msys,
+        }
+    }
+}
+
+impl Default for PathSelectionBuilder {
+    fn default() -> Self {
+        PathSelectionBuilder {
+            enabled_options: OptionKind::ALL.to_vec(),
+            option_priority: Vec::new(),
+            initial_option: None,
+            last_option: None,
+            deduplicate: false,
+            max_options: None,
+            max_input_length: None,
+            multi_line_mode: MultiLineMode::default(),
+            android_paths: false,
+            drive_remap: Vec::new(),
+            strip_diff_prefixes: false,
+            relative_base: None,
+            basename: false,
+            prefer_wsl_for_drive_paths: false,
+            makefile_escaping: false,
+            home_variable: None,
+            wsl_distro: None,
+            show_single_option: false,
+            cygwin: false,
+            msys: false,
+            osc8_link: false,
+            file_uri: false,
+            expand_env_vars: false,
+            normalize_paths: false,
+            glob_patterns: false,
+            rules: Vec::new(),
+            output_template: String::new(),
+            commit_unchanged_behavior: CommitUnchangedBehavior::default(),
+            start_at_last: false,
+            quote_spaces: false,
+        }
+    }
+}
+
+impl PathSelectionBuilder {
+    pub fn from_config(config: &Config) -> Self {
+        let mut builder = PathSelection::builder()
+            .option_priority(config.option_priority.clone())
+            .multi_line_mode(config.multi_line_mode)
+            .android_paths(config.android_paths)
+            .drive_remap(config.get_drive_remap())
+            .strip_diff_prefixes(config.strip_diff_prefixes)
+            .basename(config.offer_basename)
+            .prefer_wsl_for_drive_paths(config.prefer_wsl_for_drive_paths)
+            .makefile_escaping(config.makefile_escaping)
+            .cygwin(config.offer_cygwin)
+            .msys(config.offer_msys)
+            .osc8_link(config.offer_osc8_link)
+            .file_uri(config.offer_file_uri)
+            .expand_env_vars(config.expand_env_vars)
+            .normalize_paths(config.normalize_paths)
+            .glob_patterns(config.glob_patterns)
+            .rules(config.rules.clone())
+            .output_template(config.output_template.clone())
+            .commit_unchanged_behavior(config.commit_unchanged_behavior)
+            .quote_spaces(config.quote_paths_with_spaces)
+            .show_single_option(config.show_single_option);
+
+        if let Some(max_visible_options) = config.max_visible_options {
+            builder = builder.max_options(max_visible_options);
+        }
+        if let Some(enabled_options) = &config.enabled_options {
+            builder = builder.enabled_options(enabled_options.clone());
+        }
+        if let Some(default_option) = config.default_option {
+            builder = builder.initial_option(default_option);
+        }
+        if let Some(home) = config.get_home_folder() {
+            builder = builder.home_variable(config.home_variable, home);
+        }
+        if let Some(wsl_distro) = &config.wsl_distro {
+            builder = builder.wsl_distro(wsl_distro.clone());
+        }
+
+        builder
+    }
+
+    /// Selects the option that was pasted last initially. This takes precedence over the initial
+    /// option, but like it only applies if the option is available for the copied path.
+    pub fn last_option(mut self, last_option: OptionKind) -> Self {
+        self.last_option = Some(last_option);
+        self
+    }
+
+    pub fn enabled_options(mut self, enabled_options: Vec<OptionKind>) -> Self {
+        self.enabled_options = enabled_options;
+        self
+    }
+
+    /// Options listed here are offered first and in the given order.
+    pub fn option_priority(mut self, option_priority: Vec<OptionKind>) -> Self {
+        self.option_priority = option_priority;
+        self
+    }
+
+    /// Selects the given option initially instead of the path's own format, if it is available.
+    pub fn initial_option(mut self, initial_option: OptionKind) -> Self {
+        self.initial_option = Some(initial_option);
+        self
+    }
+
+    /// Merges options that would paste the same string, keeping the one offered first.
+    pub fn deduplicate(mut self, deduplicate: bool) -> Self {
+        self.deduplicate = deduplicate;
+        self
+    }
+
+    pub fn max_options(mut self, max_options: usize) -> Self {
+        self.max_options = Some(max_options);
+        self
+    }
+
+    /// Ignores clipboard text longer than the given number of bytes.
+    pub fn max_input_length(mut self, max_input_length: usize) -> Self {
+        self.max_input_length = Some(max_input_length);
+        self
+    }
+
+    pub fn multi_line_mode(mut self, multi_line_mode: MultiLineMode) -> Self {
+        self.multi_line_mode = multi_line_mode;
+        self
+    }
+
+    /// Recognizes Android storage paths like `/sdcard/...` and only offers them in Unix format.
+    pub fn android_paths(mut self, android_paths: bool) -> Self {
+        self.android_paths = android_paths;
+        self
+    }
+
+    /// Pairs of drive letters that are swapped to produce the remapped options. Works in both directions.
+    pub fn drive_remap(mut self, drive_remap: Vec<(char, char)>) -> Self {
+        self.drive_remap = drive_remap;
+        self
+    }
+
+    /// Removes the markers of diff headers like `--- a/` or `+++ b/` before detecting the path.
+    /// They are not re-applied on paste.
+    pub fn strip_diff_prefixes(mut self, strip_diff_prefixes: bool) -> Self {
+        self.strip_diff_prefixes = strip_diff_prefixes;
+        self
+    }
+
+    /// Absolute Windows path of the folder that relative options are computed from.
+    pub fn relative_base(mut self, relative_base: String) -> Self {
+        self.relative_base = Some(relative_base);
+        self
+    }
+
+    /// Additionally offers only the last component of the path, e.g. the file name.
+    pub fn basename(mut self, basename: bool) -> Self {
+        self.basename = basename;
+        self
+    }
+
+    /// Selects the WSL option initially for Unix paths with a drive letter like `C:/foo` and moves the
+    /// literal Unix form behind it.
+    pub fn prefer_wsl_for_drive_paths(mut self, prefer_wsl_for_drive_paths: bool) -> Self {
+        self.prefer_wsl_for_drive_paths = prefer_wsl_for_drive_paths;
+        self
+    }
+
+    /// Additionally offers the Unix and WSL forms escaped for use in Makefiles.
+    pub fn makefile_escaping(mut self, makefile_escaping: bool) -> Self {
+        self.makefile_escaping = makefile_escaping;
+        self
+    }
+
+    /// Additionally offers paths inside the given home folder with the home variable in the given style,
+    /// e.g. `$HOME/x` for `/home/me/x`. The home folder is a Unix path for the POSIX style and a
+    /// Windows path otherwise.
+    pub fn home_variable(mut self, style: VariableStyle, home: String) -> Self {
+        self.home_variable = Some((style, home));
+        self
+    }
+
+    /// Additionally offers Linux paths as seen from Windows inside the given WSL distribution, e.g.
+    /// `\\wsl$\Ubuntu\home\me` for `/home/me`.
+    pub fn wsl_distro(mut self, wsl_distro: String) -> Self {
+        self.wsl_distro = Some(wsl_distro);
+        self
+    }
+
+    /// Additionally offers the Cygwin form, e.g. `/cygdrive/c/foo`. It is always offered for copied
+    /// Cygwin paths.
+    pub fn cygwin(mut self, cygwin: bool) -> Self {
+        self.cygwin = cygwin;
+        self
+    }
+
+    /// Additionally offers the MSYS form used by Git Bash, e.g. `/c/foo`. It is always offered for
+    /// copied MSYS paths.
+    pub fn msys(mut self, msys: bool) -> Self {
+        self.msys = msys;
+        self
+    }
+
+    /// Additionally offers a clickable terminal hyperlink (OSC 8) to the `file://` URI of the path,
+    /// labeled with its name.
+    pub fn osc8_link(mut self, osc8_link: bool) -> Self {
+        self.osc8_link = osc8_link;
+        self
+    }
+
+    /// Additionally offers the `file://` URI of the path. It is always offered for copied URIs.
+    pub fn file_uri(mut self, file_uri: bool) -> Self {
+        self.file_uri = file_uri;
+        self
+    }
+
+    /// Replaces environment variables like `%USERPROFILE%` in the copied path with their values before
+    /// it is converted.
+    pub fn expand_env_vars(mut self, expand_env_vars: bool) -> Self {
+        self.expand_env_vars = expand_env_vars;
+        self
+    }
+
+    /// Collapses repeated separators and resolves `.` and `..` segments in the copied path before it is
+    /// converted, e.g. `a//b/../c` to `a/c`.
+    pub fn normalize_paths(mut self, normalize_paths: bool) -> Self {
+        self.normalize_paths = normalize_paths;
+        self
+    }
+
+    /// Accepts glob patterns like `C:\logs\*.txt` or `/var/log/**/*.log` and keeps their wildcards
+    /// `*`, `**` and `?` in the converted paths.
+    pub fn glob_patterns(mut self, glob_patterns: bool) -> Self {
+        self.glob_patterns = glob_patterns;
+        self
+    }
+
+    /// Additionally offers the output of each custom rule whose pattern matches the copied path.
+    pub fn rules(mut self, rules: Vec<ConversionRule>) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    /// Wraps the pasted path into the template, where `{path}` is replaced by the path.
+    pub fn output_template(mut self, output_template: String) -> Self {
+        self.output_template = output_template;
+        self
+    }
+
+    /// Whether committing without cycling pastes the copied text as it is instead of the converted path.
+    pub fn commit_unchanged_behavior(
+        mut self,
+        commit_unchanged_behavior: CommitUnchangedBehavior,
+    ) -> Self {
+        self.commit_unchanged_behavior = commit_unchanged_behavior;
+        self
+    }
+
+    /// Selects the last option initially, like cycling backwards from the first one. This counts as
+    /// cycling, so the selected option is pasted even if the copied text would be pasted unchanged.
+    pub fn start_at_last(mut self, start_at_last: bool) -> Self {
+        self.start_at_last = start_at_last;
+        self
+    }
+
+    /// Quotes paths that contain whitespace for the shell of their format, unless the copied path
+    /// was already wrapped in quotes or similar, which are kept instead.
+    pub fn quote_spaces(mut self, quote_spaces: bool) -> Self {
+        self.quote_spaces = quote_spaces;
+        self
+    }
+
+    /// Builds a selection even if there is only one option for the path, to confirm that it has been
+    /// recognized. Otherwise there is nothing to select, so the text is pasted as usual.
+    pub fn show_single_option(mut self, show_single_option: bool) -> Self {
+        self.show_single_option = show_single_option;
+        self
+    }
+
+    pub fn build(&self, raw_path: String) -> Option<PathSelection> {
+        if self
+            .max_input_length
+            .is_some_and(|max_input_length| raw_path.len() > max_input_length)
+        {
             return None;
         }
 
-        let initial_path_type = path.get_type();
-        let initial_selection = ok_options
-            .iter()
-            .position(|x| x.get_type() == initial_path_type)
-            .unwrap_or(0);
+        let (mut options, source_kind, decoration, copied) = if raw_path.trim_end().contains('\n') {
+            let (options, initial_type) =
+                PathSelection::get_multi_line_options(&raw_path, self.multi_line_mode)?;
+            (
+                options,
+                OptionKind::from(initial_type),
+                Decoration::default(),
+                raw_path.clone(),
+            )
+        } else {
+            let text = if self.strip_diff_prefixes {
+                PathSelection::strip_diff_prefix(&raw_path).unwrap_or(&raw_path)
+            } else {
+                &raw_path
+            };
+            let text = if self.expand_env_vars {
+                path::expand_env_vars(text)
+            } else {
+                text.to_string()
+            };
+            let text = if self.normalize_paths {
+                path::normalize_path(&text)
+            } else {
+                text
+            };
+            let text = if self.glob_patterns {
+                path::mask_glob_chars(&text)
+            } else {
+                text
+            };
+            let (path, source_kind, decoration) = PathSelection::get_initial_path(text)?;
+            let mut options = PathSelection::get_path_options(
+                path.as_ref(),
+                &self.enabled_options,
+                &self.drive_remap,
+                self.relative_base.as_deref(),
+            );
+            if self.basename {
+                options.extend(PathSelection::get_basename_option(path.as_ref()));
+            }
+            let path_string = if self.glob_patterns {
+                for option in &mut options {
+                    option.path = path::unmask_glob_chars(&option.path);
+                }
+                path::unmask_glob_chars(&path.as_string())
+            } else {
+                path.as_string()
+            };
+            if let Some((style, home)) = &self.home_variable {
+                options.extend(PathSelection::get_home_variable_option(
+                    &options, *style, home,
+                ));
+            }
+            if let Some(wsl_distro) = &self.wsl_distro {
+                options.extend(PathSelection::get_wsl_unc_option(
+                    &options,
+                    source_kind,
+                    wsl_distro,
+                ));
+            }
+            if self.makefile_escaping {
+                options.extend(PathSelection::get_makefile_options(&options));
+            }
+            if self.osc8_link {
+                options.extend(PathSelection::get_osc8_link_option(&options));
+            }
+            if self.file_uri || source_kind == OptionKind::FileUri {
+                options.extend(PathSelection::get_file_uri_option(&options));
+            }
+            options.extend(PathSelection::get_rule_options(&path_string, &self.rules));
+            if self.android_paths
+                && path.get_type() == PathType::Unix
+                && path::is_android_path(&path_string)
+            {
+                // Converting these would only produce misleading Windows paths like `\sdcard\...`
+                options.retain(|x| x.kind == OptionKind::Unix);
+            }
+            (options, source_kind, decoration, path_string)
+        };
+
+        options.retain(|x| self.enabled_options.contains(&x.kind));
+        if !self.cygwin && source_kind != OptionKind::Cygwin {
+            options.retain(|x| x.kind != OptionKind::Cygwin);
+        }
+        if !self.msys && source_kind != OptionKind::Msys {
+            options.retain(|x| x.kind != OptionKind::Msys);
+        }
+        self.prioritize_options(&mut options);
+
+        let default_kind = if self.prefer_wsl_for_drive_paths
+            && source_kind == OptionKind::Unix
+            && UNIX_DRIVE_PATH_REGEX.is_match(&raw_path)
+            && PathSelection::promote_wsl_option(&mut options)
+        {
+            OptionKind::Wsl
+        } else {
+            source_kind
+        };
+
+        match options.len() {
+            0 => return None,
+            // If there is only one option, there is nothing to select
+            1 if !self.show_single_option => return None,
+            _ => {}
+        }
+
+        let position = |kind: Option<OptionKind>| {
+            kind.and_then(|kind| options.iter().position(|x| x.kind == kind))
+        };
+        let initial_selection = match (position(self.last_option), position(self.initial_option)) {
+            // The last pasted format sticks, even if the copied path is already in that format
+            (Some(last), _) => last,
+            // The copied path has already been converted to the initial option, e.g. by an
+            // earlier paste, so it is left in its own format instead of converting it again
+            (None, Some(initial)) if options[initial].path != copied => initial,
+            _ => options
+                .iter()
+                .position(|x| x.kind == default_kind)
+                .unwrap_or(0),
+        };
+
+        let initial_selection = if self.start_at_last {
+            options.len() - 1
+        } else {
+            initial_selection
+        };
 
         Some(PathSelection {
-            options: ok_options,
+            options,
             current: initial_selection,
+            decoration,
+            original: raw_path,
+            cycled: self.start_at_last,
+            source_kind,
+            output_template: self.output_template.clone(),
+            commit_unchanged_behavior: self.commit_unchanged_behavior,
+            quote_spaces: self.quote_spaces,
         })
     }
 
+    fn prioritize_options(&self, options: &mut Vec<PathOption>) {
+        // Options without a configured priority keep their order behind the prioritized ones
+        options.sort_by_key(|x| {
+            self.option_priority
+                .iter()
+                .position(|kind| *kind == x.kind)
+                .unwrap_or(usize::MAX)
+        });
+
+        if self.deduplicate {
+            let mut seen_paths = HashSet::new();
+            options.retain(|x| seen_paths.insert(x.path.clone()));
+        }
+
+        if let Some(max_options) = self.max_options {
+            options.truncate(max_options.max(1));
+        }
+    }
+}
+
+impl PathSelection {
+    pub fn builder() -> PathSelectionBuilder {
+        PathSelectionBuilder::default()
+    }
+
     pub fn next(&mut self) {
         self.current = (self.current + 1) % self.options.len();
+        self.cycled = true;
     }
 
     pub fn previous(&mut self) {
         self.current = (self.current + self.options.len() - 1) % self.options.len();
+        self.cycled = true;
+    }
+
+    /// Selects the option at the given index. Returns whether there is an option at that index.
+    pub fn select_index(&mut self, index: usize) -> bool {
+        if index >= self.options.len() {
+            return false;
+        }
+        self.current = index;
+        self.cycled = true;
+        true
+    }
+
+    /// Selects the option of the given kind. Returns whether it is available.
+    pub fn select(&mut self, kind: OptionKind) -> bool {
+        match self.options.iter().position(|x| x.kind == kind) {
+            Some(index) => self.select_index(index),
+            None => false,
+        }
+    }
+
+    pub fn get_original_string(&self) -> String {
+        self.original.clone()
+    }
+
+    pub fn get_selected_kind(&self) -> OptionKind {
+        self.options[self.current].kind
     }
 
     pub fn get_selected_path_string(&self) -> String {
-        self.options[self.current].as_string()
+        let path = self.decoration.apply(&self.options[self.current].path);
+        let kind = self.options[self.current].kind;
+        if self.quote_spaces
+            && self.decoration.wrapper.is_none()
+            && path.contains(char::is_whitespace)
+        {
+            kind.quote(&path).unwrap_or(path)
+        } else {
+            path
+        }
+    }
+
+    /// Returns the text to search for on commit, which is the selected path without shell quotes.
+    pub fn get_search_string(&self) -> String {
+        if self.commits_original() {
+            self.get_original_string()
+        } else {
+            self.decoration.apply(&self.options[self.current].path)
+        }
+    }
+
+    /// Whether committing pastes the copied text as it is, because the selection hasn't been cycled.
+    pub fn commits_original(&self) -> bool {
+        !self.cycled && self.commit_unchanged_behavior == CommitUnchangedBehavior::LiteralOriginal
+    }
+
+    /// Returns the text that is pasted when the selection is committed, with the output template applied
+    /// to the selected path. The literal original is pasted exactly as it was copied.
+    pub fn get_output_string(&self) -> String {
+        if self.commits_original() {
+            self.get_original_string()
+        } else {
+            config::apply_output_template(&self.output_template, &self.get_selected_path_string())
+        }
     }
 
     pub fn get_info(&self) -> PathSelectionInfo {
@@ -64,30 +913,1416 @@ impl PathSelection {
             .options
             .iter()
             .map(|x| PathSelectionInfoEntry {
-                label: match x.get_type() {
-                    PathType::Windows => "Win".to_string(),
-                    PathType::Unix => "Unix".to_string(),
-                    PathType::Wsl => "WSL".to_string(),
-                },
-                path: x.as_string(),
+                label: x.kind.label().to_string(),
+                path: x.path.clone(),
+                source_label: self.source_kind.label().to_string(),
+                lossless: x.lossless,
+                exists: x.exists,
+                metadata: path::get_metadata(&x.path),
             })
             .collect();
 
         PathSelectionInfo {
             options,
             selected: self.current,
+            output: self.get_output_string(),
+            committed: false,
+        }
+    }
+
+    fn get_initial_path(text: String) -> Option<(Box<dyn Path>, OptionKind, Decoration)> {
+        // Wrapped paths are converted without their wrapper, which is re-applied on paste
+        for wrapper in Wrapper::ALL {
+            if let Some((path, kind, suffix)) = wrapper
+                .strip(&text)
+                .and_then(PathSelection::detect_path_with_suffix)
+            {
+                let decoration = Decoration {
+                    wrapper: Some(wrapper),
+                    suffix,
+                };
+                return Some((path, kind, decoration));
+            }
+        }
+
+        let (path, kind, suffix) = PathSelection::detect_path_with_suffix(&text)?;
+        Some((
+            path,
+            kind,
+            Decoration {
+                wrapper: None,
+                suffix,
+            },
+        ))
+    }
+
+    fn strip_diff_prefix(text: &str) -> Option<&str> {
+        DIFF_PREFIX_REGEX
+            .captures(text)
+            .and_then(|captures| captures.get(1))
+            .map(|path| path.as_str())
+    }
+
+    fn detect_path_with_suffix(text: &str) -> Option<(Box<dyn Path>, OptionKind, String)> {
+        // Devices have no equivalent in other formats, so they are always pasted as they are
+        if DEVICE_PATH_REGEX.is_match(text) {
+            return None;
+        }
+
+        if let Some(captures) = LOCATION_SUFFIX_REGEX.captures(text) {
+            let (path, suffix) = (&captures[1], &captures[2]);
+
+            // A single letter followed by digits is a drive-relative path like `C:42`
+            if !DRIVE_LETTER_REGEX.is_match(path) {
+                if let Some((path, kind)) = PathSelection::detect_path(path) {
+                    return Some((path, kind, suffix.to_string()));
+                }
+            }
+        }
+
+        PathSelection::detect_path(text).map(|(path, kind)| (path, kind, String::new()))
+    }
+
+    /// Like [`path::detect_path`], but also accepts UNC paths with forward slashes (`//server/share`)
+    /// as emitted by some cross-platform tools. These are converted as regular UNC paths.
+    /// `file://` URIs are converted as the path they point to.
+    fn detect_path(text: &str) -> Option<(Box<dyn Path>, OptionKind)> {
+        if let Some(decoded) = path::from_file_uri(text) {
+            let (path, _) = PathSelection::detect_path(&decoded)?;
+            return Some((path, OptionKind::FileUri));
+        }
+
+        if FORWARD_UNC_REGEX.is_match(text) {
+            let path = WindowsPath::new(text.replace('/', "\\")).ok()?;
+            return Some((Box::new(path), OptionKind::UncUnix));
+        }
+
+        let path = path::detect_path(text)?;
+        let kind = OptionKind::from(path.get_type());
+        Some((path, kind))
+    }
+
+    fn get_path_options(
+        path: &dyn Path,
+        enabled_options: &[OptionKind],
+        drive_remap: &[(char, char)],
+        relative_base: Option<&str>,
+    ) -> Vec<PathOption> {
+        let mut options: Vec<PathOption> = path::get_conversions(path)
+            .into_iter()
+            .map(|x| PathOption::new(OptionKind::from(x.path_type), x.path))
+            .collect();
+
+        // These options require file system lookups, which are skipped if they aren't needed
+        if enabled_options.contains(&OptionKind::WindowsShort) {
+            options.extend(PathSelection::get_short_option(&options));
+        }
+
+        if enabled_options.contains(&OptionKind::Unc)
+            || enabled_options.contains(&OptionKind::UncUnix)
+        {
+            options.extend(PathSelection::get_unc_options(&options));
+        }
+
+        if enabled_options.contains(&OptionKind::WindowsCanonical) {
+            options.extend(PathSelection::get_canonical_option(&options));
+        }
+
+        if enabled_options.contains(&OptionKind::MappedDrive) {
+            options.extend(PathSelection::get_mapped_drive_option(&options));
+        }
+
+        options.extend(PathSelection::get_remapped_options(&options, drive_remap));
+        options.extend(PathSelection::get_wsl_distro_option(&options));
+        options.extend(PathSelection::get_forward_unc_option(&options));
+
+        if let Some(relative_base) = relative_base {
+            options.extend(PathSelection::get_relative_options(&options, relative_base));
+        }
+
+        for option in &mut options {
+            option.lossless = option.is_lossless_for(path);
+            option.exists = option.check_exists();
         }
+
+        options
     }
 
-    fn get_initial_path(path: String) -> Option<Box<dyn Path>> {
-        if let Ok(windows_path) = WindowsPath::new(path.clone()) {
-            Some(Box::new(windows_path))
-        } else if let Ok(unix_path) = UnixPath::new(path.clone()) {
-            Some(Box::new(unix_path))
-        } else if let Ok(wsl_path) = WslPath::new(path) {
-            Some(Box::new(wsl_path))
+    /// Converts every line containing a path, keeping other lines as they are.
+    /// Only formats that all of the paths can be converted to are offered.
+    fn get_multi_line_options(
+        text: &str,
+        mode: MultiLineMode,
+    ) -> Option<(Vec<PathOption>, PathType)> {
+        if mode == MultiLineMode::Disabled {
+            return None;
+        }
+
+        let line_separator = if text.contains("\r\n") { "\r\n" } else { "\n" };
+        let split_line = match mode {
+            MultiLineMode::Lines => PathLine::whole,
+            _ => PathLine::new,
+        };
+        let lines: Vec<PathLine> = text.split(line_separator).map(split_line).collect();
+
+        let initial_type = lines.iter().find_map(|line| line.path.as_ref())?.get_type();
+
+        let options = [
+            PathType::Windows,
+            PathType::Unix,
+            PathType::Wsl,
+            PathType::Cygwin,
+            PathType::Msys,
+        ]
+        .into_iter()
+        .filter_map(|path_type| {
+            let converted_lines: Option<Vec<String>> =
+                lines.iter().map(|line| line.convert(path_type)).collect();
+            Some(PathOption::new(
+                OptionKind::from(path_type),
+                converted_lines?.join(line_separator),
+            ))
+        })
+        .collect();
+
+        Some((options, initial_type))
+    }
+
+    fn get_unc_options(options: &[PathOption]) -> Vec<PathOption> {
+        let Some(windows_path) = options.iter().find(|x| x.kind == OptionKind::Windows) else {
+            return Vec::new();
+        };
+        let Some(captures) = DRIVE_PATH_REGEX.captures(&windows_path.path) else {
+            return Vec::new();
+        };
+
+        // Only mapped network drives have a UNC target
+        let drive_letter = captures[1].chars().next().unwrap();
+        let Ok(target) = system::get_network_drive_target(drive_letter) else {
+            return Vec::new();
+        };
+
+        let unc_path = format!(
+            "{}{}",
+            target.trim_end_matches('\\'),
+            captures.get(2).map_or("", |x| x.as_str())
+        );
+
+        let unc_unix_path = unc_path.replace('\\', "/");
+
+        vec![
+            PathOption::new(OptionKind::Unc, unc_path),
+            PathOption::new(OptionKind::UncUnix, unc_unix_path),
+        ]
+    }
+
+    fn get_mapped_drive_option(options: &[PathOption]) -> Option<PathOption> {
+        let windows_path = &options.iter().find(|x| x.kind == OptionKind::Windows)?.path;
+        if !windows_path.starts_with(r"\\") {
+            return None;
+        }
+
+        let mappings = system::get_network_drive_mappings();
+        let drive_path = PathSelection::map_unc_to_drive(windows_path, &mappings)?;
+        Some(PathOption::new(OptionKind::MappedDrive, drive_path))
+    }
+
+    /// Replaces the share of a UNC path with the drive letter it is mapped to, if any.
+    fn map_unc_to_drive(unc_path: &str, mappings: &[(char, String)]) -> Option<String> {
+        mappings.iter().find_map(|(drive_letter, target)| {
+            let target = target.trim_end_matches('\\');
+            let prefix = unc_path.get(..target.len())?;
+            let rest = &unc_path[target.len()..];
+
+            // The share has to match completely, e.g. `\\nas\share` must not match `\\nas\shared`
+            if prefix.eq_ignore_ascii_case(target) && (rest.is_empty() || rest.starts_with('\\')) {
+                Some(format!("{}:{}", drive_letter, rest))
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Swaps the drive letter of the Windows option and converts the result into the base formats.
+    fn get_remapped_options(
+        options: &[PathOption],
+        drive_remap: &[(char, char)],
+    ) -> Vec<PathOption> {
+        let Some(windows_path) = options.iter().find(|x| x.kind == OptionKind::Windows) else {
+            return Vec::new();
+        };
+        let Some(captures) = DRIVE_PATH_REGEX.captures(&windows_path.path) else {
+            return Vec::new();
+        };
+
+        let drive_letter = captures[1].chars().next().unwrap();
+        let Some(remapped_letter) = drive_remap.iter().find_map(|(from, to)| {
+            if from.eq_ignore_ascii_case(&drive_letter) {
+                Some(*to)
+            } else if to.eq_ignore_ascii_case(&drive_letter) {
+                Some(*from)
+            } else {
+                None
+            }
+        }) else {
+            return Vec::new();
+        };
+
+        let remapped_path = format!(
+            "{}:{}",
+            remapped_letter.to_ascii_uppercase(),
+            captures.get(2).map_or("", |x| x.as_str())
+        );
+        let Ok(remapped_path) = WindowsPath::new(remapped_path) else {
+            return Vec::new();
+        };
+
+        path::get_conversions(&remapped_path)
+            .into_iter()
+            .filter_map(|x| {
+                let kind = match x.path_type {
+                    PathType::Windows => OptionKind::Remapped,
+                    PathType::Unix => OptionKind::RemappedUnix,
+                    PathType::Wsl => OptionKind::RemappedWsl,
+                    PathType::Cygwin | PathType::Msys => return None,
+                };
+                Some(PathOption::new(kind, x.path))
+            })
+            .collect()
+    }
+
+    /// Offers the Linux path of a file inside a WSL distribution's file system.
+    fn get_wsl_distro_option(options: &[PathOption]) -> Option<PathOption> {
+        let windows_path = &options.iter().find(|x| x.kind == OptionKind::Windows)?.path;
+        let (_, linux_path) = path::parse_wsl_unc_path(windows_path)?;
+        Some(PathOption::new(OptionKind::WslDistro, linux_path))
+    }
+
+    /// Offers a Linux path that has no Windows equivalent, like `/home/me`, as seen from Windows
+    /// inside the WSL distribution. Paths on Windows drives like `/mnt/c` are left to the other options.
+    fn get_wsl_unc_option(
+        options: &[PathOption],
+        source_kind: OptionKind,
+        distro: &str,
+    ) -> Option<PathOption> {
+        if source_kind != OptionKind::Unix || options.iter().any(|x| x.kind == OptionKind::Wsl) {
+            return None;
+        }
+
+        let linux_path = &options.iter().find(|x| x.kind == OptionKind::Unix)?.path;
+        let wsl_unc_path = path::to_wsl_unc_path(distro, linux_path)?;
+        Some(PathOption::new(OptionKind::WslUnc, wsl_unc_path))
+    }
+
+    /// Offers the path with the casing it has on disk, if that differs from the copied one.
+    fn get_canonical_option(options: &[PathOption]) -> Option<PathOption> {
+        let windows_path = &options.iter().find(|x| x.kind == OptionKind::Windows)?.path;
+        if !DRIVE_PATH_REGEX.is_match(windows_path) {
+            return None;
+        }
+
+        let final_path = system::get_final_path_name(windows_path).ok()?;
+        let canonical_path = PathSelection::restore_trailing_separator(windows_path, final_path);
+        PathSelection::is_recased(windows_path, &canonical_path)
+            .then(|| PathOption::new(OptionKind::WindowsCanonical, canonical_path))
+    }
+
+    fn restore_trailing_separator(original: &str, path: String) -> String {
+        if original.ends_with('\\') && !path.ends_with('\\') {
+            format!("{}\\", path)
         } else {
-            None
+            path
+        }
+    }
+
+    /// Whether the paths only differ in casing. Resolved links or substituted drives are not offered,
+    /// since they would point somewhere else than the copied path.
+    fn is_recased(original: &str, path: &str) -> bool {
+        original != path && original.eq_ignore_ascii_case(path)
+    }
+
+    fn get_relative_options(options: &[PathOption], relative_base: &str) -> Vec<PathOption> {
+        let relative_path = options
+            .iter()
+            .find(|x| x.kind == OptionKind::Windows)
+            .and_then(|windows_path| path::get_relative_path(relative_base, &windows_path.path));
+        let Some(relative_path) = relative_path else {
+            return Vec::new();
+        };
+
+        let relative_unix_path = relative_path.replace('\\', "/");
+        vec![
+            PathOption::new(OptionKind::Relative, relative_path),
+            PathOption::new(OptionKind::RelativeUnix, relative_unix_path),
+        ]
+    }
+
+    /// Offers UNC paths with forward slashes, since the Unix conversion doesn't accept them.
+    fn get_forward_unc_option(options: &[PathOption]) -> Option<PathOption> {
+        let windows_path = &options.iter().find(|x| x.kind == OptionKind::Windows)?.path;
+        if !windows_path.starts_with(r"\\") || options.iter().any(|x| x.kind == OptionKind::UncUnix)
+        {
+            return None;
+        }
+
+        Some(PathOption::new(
+            OptionKind::UncUnix,
+            windows_path.replace('\\', "/"),
+        ))
+    }
+
+    /// Moves the Unix option behind the WSL option. Returns whether there is a WSL option.
+    fn promote_wsl_option(options: &mut Vec<PathOption>) -> bool {
+        let Some(wsl_index) = options.iter().position(|x| x.kind == OptionKind::Wsl) else {
+            return false;
+        };
+
+        if let Some(unix_index) = options.iter().position(|x| x.kind == OptionKind::Unix) {
+            if unix_index < wsl_index {
+                let unix_option = options.remove(unix_index);
+                options.insert(wsl_index, unix_option);
+            }
+        }
+
+        true
+    }
+
+    /// Offers the Unix and WSL options escaped for Makefiles, if they contain special characters.
+    fn get_makefile_options(options: &[PathOption]) -> Vec<PathOption> {
+        options
+            .iter()
+            .filter_map(|x| {
+                let kind = match x.kind {
+                    OptionKind::Unix => OptionKind::MakeUnix,
+                    OptionKind::Wsl => OptionKind::MakeWsl,
+                    _ => return None,
+                };
+                let escaped = PathSelection::escape_for_makefile(&x.path);
+                (escaped != x.path).then(|| PathOption::new(kind, escaped))
+            })
+            .collect()
+    }
+
+    fn escape_for_makefile(path: &str) -> String {
+        path.replace('$', "$$")
+            .replace(' ', "\\ ")
+            .replace('#', "\\#")
+    }
+
+    fn get_home_variable_option(
+        options: &[PathOption],
+        style: VariableStyle,
+        home: &str,
+    ) -> Option<PathOption> {
+        let (kind, variable, separator) = match style {
+            VariableStyle::Disabled => return None,
+            VariableStyle::Posix => (OptionKind::Unix, "$HOME", '/'),
+            VariableStyle::PowerShell => (OptionKind::Windows, "$env:USERPROFILE", '\\'),
+            VariableStyle::Cmd => (OptionKind::Windows, "%USERPROFILE%", '\\'),
+        };
+
+        let home = home.trim_end_matches(separator);
+        let path = &options.iter().find(|x| x.kind == kind)?.path;
+        // Windows paths are case-insensitive
+        let rest = path
+            .get(..home.len())
+            .filter(|prefix| {
+                !home.is_empty()
+                    && (*prefix == home
+                        || kind == OptionKind::Windows && prefix.eq_ignore_ascii_case(home))
+            })
+            .map(|_| &path[home.len()..])?;
+
+        // A folder that only starts with the same name, like `/home/meow` for `/home/me`
+        if !rest.is_empty() && !rest.starts_with(separator) {
+            return None;
+        }
+
+        Some(PathOption::new(
+            OptionKind::HomeVariable,
+            format!("{variable}{rest}"),
+        ))
+    }
+
+    /// Offers the output of every rule that matches the path.
+    fn get_rule_options(path: &str, rules: &[ConversionRule]) -> Vec<PathOption> {
+        rules
+            .iter()
+            .filter_map(|rule| rule.apply(path))
+            .map(|output| PathOption::new(OptionKind::Rule, output))
+            .collect()
+    }
+
+    /// Offers the path as an OSC 8 escape sequence, which terminals show as a link with the name of
+    /// the path as its text.
+    fn get_osc8_link_option(options: &[PathOption]) -> Option<PathOption> {
+        let windows_path = &options.iter().find(|x| x.kind == OptionKind::Windows)?.path;
+        let uri = path::to_file_uri(windows_path)?;
+        let label = path::get_basename(windows_path).unwrap_or(windows_path);
+        Some(PathOption::new(
+            OptionKind::Osc8Link,
+            format!("\x1b]8;;{uri}\x1b\\{label}\x1b]8;;\x1b\\"),
+        ))
+    }
+
+    fn get_file_uri_option(options: &[PathOption]) -> Option<PathOption> {
+        let windows_path = &options.iter().find(|x| x.kind == OptionKind::Windows)?.path;
+        let uri = path::to_file_uri(windows_path)?;
+        Some(PathOption::new(OptionKind::FileUri, uri))
+    }
+
+    fn get_basename_option(path: &dyn Path) -> Option<PathOption> {
+        let path = path.as_string();
+        let basename = path::get_basename(&path)?;
+        Some(PathOption::new(OptionKind::Basename, basename.to_string()))
+    }
+
+    fn get_short_option(options: &[PathOption]) -> Option<PathOption> {
+        // Short names can only be looked up for paths that exist on disk
+        let windows_path = &options.iter().find(|x| x.kind == OptionKind::Windows)?.path;
+        let short_path = system::get_short_path_name(windows_path).ok()?;
+
+        if short_path == *windows_path {
+            // The path has no short name that differs from its long form
+            return None;
+        }
+
+        Some(PathOption::new(OptionKind::WindowsShort, short_path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_labels(selection: &PathSelection) -> Vec<String> {
+        selection
+            .get_info()
+            .options
+            .into_iter()
+            .map(|x| x.label)
+            .collect()
+    }
+
+    /// Collects the pasted string of every option, starting at the initial selection.
+    fn get_outputs(input: &str) -> Vec<String> {
+        let mut selection = PathSelection::builder().build(input.to_string()).unwrap();
+        let mut outputs = Vec::new();
+        for _ in 0..selection.get_info().options.len() {
+            outputs.push(selection.get_selected_path_string());
+            selection.next();
+        }
+        outputs
+    }
+
+    #[test]
+    fn test_default_option_order() {
+        let selection = PathSelection::builder()
+            .build(r"C:\Users".to_string())
+            .unwrap();
+        assert_eq!(get_labels(&selection), vec!["Win", "Unix", "WSL"]);
+        assert_eq!(selection.get_selected_path_string(), r"C:\Users");
+    }
+
+    #[test]
+    fn test_option_priority() {
+        let selection = PathSelection::builder()
+            .option_priority(vec![OptionKind::Wsl, OptionKind::Unix])
+            .build(r"C:\Users".to_string())
+            .unwrap();
+        assert_eq!(get_labels(&selection), vec!["WSL", "Unix", "Win"]);
+        assert_eq!(selection.get_selected_path_string(), r"C:\Users");
+    }
+
+    #[test]
+    fn test_max_visible_options() {
+        let selection = PathSelection::builder()
+            .max_options(2)
+            .build(r"C:\Users".to_string())
+            .unwrap();
+        assert_eq!(get_labels(&selection), vec!["Win", "Unix"]);
+
+        let builder = PathSelection::builder().max_options(1);
+        assert!(builder.build(r"C:\Users".to_string()).is_none());
+    }
+
+    #[test]
+    fn test_wrapped_paths() {
+        let pairs = vec![
+            (
+                r"`C:\Users`",
+                vec![r"`C:\Users`", "`C:/Users`", "`/mnt/c/Users`"],
+            ),
+            (
+                r"<C:\Users>",
+                vec![r"<C:\Users>", "<C:/Users>", "</mnt/c/Users>"],
+            ),
+            ("`/home/user`", vec!["`/home/user`", r"`\home\user`"]),
+        ];
+
+        for (input, expected) in pairs {
+            assert_eq!(get_outputs(input), expected);
+        }
+    }
+
+    #[test]
+    fn test_location_suffix() {
+        let pairs = vec![
+            (
+                r"C:\src\main.rs:42:10",
+                vec![
+                    r"C:\src\main.rs:42:10",
+                    "C:/src/main.rs:42:10",
+                    "/mnt/c/src/main.rs:42:10",
+                ],
+            ),
+            (
+                r"C:\src\main.rs:42",
+                vec![
+                    r"C:\src\main.rs:42",
+                    "C:/src/main.rs:42",
+                    "/mnt/c/src/main.rs:42",
+                ],
+            ),
+            (
+                "/src/main.rs:42",
+                vec!["/src/main.rs:42", r"\src\main.rs:42"],
+            ),
+            (
+                "/src/main.rs:42:7",
+                vec!["/src/main.rs:42:7", r"\src\main.rs:42:7"],
+            ),
+            (r"C:\", vec![r"C:\", "C:/", "/mnt/c/"]),
+        ];
+
+        for (input, expected) in pairs {
+            assert_eq!(get_outputs(input), expected);
+        }
+    }
+
+    #[test]
+    fn test_multi_line_first_token() {
+        let input = "C:\\src\\a.txt  100%\r\n  Total copied: 2\r\nC:\\src\\b.txt\tdone";
+
+        assert!(PathSelection::builder().build(input.to_string()).is_none());
+
+        let mut selection = PathSelection::builder()
+            .multi_line_mode(MultiLineMode::FirstToken)
+            .build(input.to_string())
+            .unwrap();
+        assert_eq!(selection.get_selected_path_string(), input);
+
+        selection.next();
+        assert_eq!(
+            selection.get_selected_path_string(),
+            "C:/src/a.txt  100%\r\n  Total copied: 2\r\nC:/src/b.txt\tdone"
+        );
+
+        selection.next();
+        assert_eq!(
+            selection.get_selected_path_string(),
+            "/mnt/c/src/a.txt  100%\r\n  Total copied: 2\r\n/mnt/c/src/b.txt\tdone"
+        );
+    }
+
+    #[test]
+    fn test_builder_options() {
+        let builder =
+            PathSelection::builder().enabled_options(vec![OptionKind::Wsl, OptionKind::Unix]);
+        let selection = builder.build(r"C:\Users".to_string()).unwrap();
+        assert_eq!(get_labels(&selection), vec!["Unix", "WSL"]);
+
+        let builder = PathSelection::builder().initial_option(OptionKind::Wsl);
+        let selection = builder.build(r"C:\Users".to_string()).unwrap();
+        assert_eq!(selection.get_selected_path_string(), "/mnt/c/Users");
+
+        // The initial option falls back to the path's own format if it isn't available
+        let selection = builder.build("/home/user".to_string()).unwrap();
+        assert_eq!(selection.get_selected_path_string(), "/home/user");
+
+        // A path that is already in the initial option's format is left in its own format
+        let builder = PathSelection::builder().initial_option(OptionKind::Windows);
+        let selection = builder.build(r"C:\Users".to_string()).unwrap();
+        assert_eq!(selection.get_selected_kind(), OptionKind::Windows);
+        assert_eq!(selection.get_selected_path_string(), r"C:\Users");
+
+        let selection = PathSelection::builder()
+            .initial_option(OptionKind::Wsl)
+            .build("/mnt/c/foo".to_string())
+            .unwrap();
+        assert_eq!(selection.get_selected_kind(), OptionKind::Unix);
+        assert_eq!(selection.get_selected_path_string(), "/mnt/c/foo");
+
+        let builder = PathSelection::builder().deduplicate(true);
+        let selection = builder.build("/mnt/c/Users".to_string()).unwrap();
+        assert_eq!(get_labels(&selection), vec!["Win", "Unix"]);
+
+        let builder = PathSelection::builder().max_input_length(5);
+        assert!(builder.build(r"C:\Users".to_string()).is_none());
+    }
+
+    #[test]
+    fn test_android_paths() {
+        let input = "/sdcard/DCIM/photo.jpg".to_string();
+        assert!(PathSelection::builder().build(input.clone()).is_some());
+        assert!(PathSelection::builder()
+            .android_paths(true)
+            .build(input)
+            .is_none());
+
+        let selection = PathSelection::builder()
+            .android_paths(true)
+            .build("/home/user".to_string())
+            .unwrap();
+        assert_eq!(get_labels(&selection), vec!["Win", "Unix"]);
+    }
+
+    #[test]
+    fn test_show_single_option() {
+        let builder = PathSelection::builder()
+            .android_paths(true)
+            .show_single_option(true);
+        let selection = builder.build("/sdcard/DCIM/photo.jpg".to_string()).unwrap();
+        assert_eq!(get_labels(&selection), vec!["Unix"]);
+        assert_eq!(selection.get_output_string(), "/sdcard/DCIM/photo.jpg");
+
+        // Without any option, there is still nothing to show
+        let selection = PathSelection::builder()
+            .enabled_options(vec![OptionKind::Rule])
+            .show_single_option(true)
+            .build(r"C:\Users".to_string());
+        assert!(selection.is_none());
+    }
+
+    #[test]
+    fn test_option_metadata() {
+        let info = PathSelection::builder()
+            .build("/home/user/file.txt".to_string())
+            .unwrap()
+            .get_info();
+
+        assert!(info.options.iter().all(|x| x.source_label == "Unix"));
+        assert!(info.options.iter().all(|x| x.lossless));
+        assert_eq!(info.options[0].exists, Some(false));
+        assert_eq!(info.options[1].exists, None);
+        assert_eq!(info.options[0].metadata.separator, Some('\\'));
+        assert_eq!(info.options[1].metadata.separator, Some('/'));
+        assert!(info.options.iter().all(|x| x.metadata.absolute));
+    }
+
+    #[test]
+    fn test_map_unc_to_drive() {
+        let mappings = vec![
+            ('Y', r"\\nas\shared".to_string()),
+            ('Z', r"\\nas\share\".to_string()),
+        ];
+
+        let pairs = vec![
+            (r"\\nas\share\folder\file.txt", Some(r"Z:\folder\file.txt")),
+            (r"\\NAS\Share", Some("Z:")),
+            (r"\\nas\shared\folder", Some(r"Y:\folder")),
+            (r"\\nas\sharefolder", None),
+            (r"\\other\share\folder", None),
+        ];
+
+        for (input, expected) in pairs {
+            assert_eq!(
+                PathSelection::map_unc_to_drive(input, &mappings).as_deref(),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_drive_remap() {
+        let builder = PathSelection::builder().drive_remap(vec![('D', 'E')]);
+
+        let selection = builder.build(r"D:\data\file.txt".to_string()).unwrap();
+        assert_eq!(
+            get_labels(&selection),
+            vec!["Win", "Unix", "WSL", "Remap", "Remap/", "Remap WSL"]
+        );
+        assert_eq!(selection.options[3].path, r"E:\data\file.txt");
+        assert_eq!(selection.options[4].path, "E:/data/file.txt");
+        assert_eq!(selection.options[5].path, "/mnt/e/data/file.txt");
+
+        // The mapping applies in both directions and to other source formats
+        let selection = builder.build("e:/data".to_string()).unwrap();
+        assert_eq!(
+            get_labels(&selection),
+            vec!["Win", "Unix", "WSL", "Remap", "Remap/", "Remap WSL"]
+        );
+        assert_eq!(selection.options[3].path, r"D:\data");
+
+        let selection = builder.build(r"C:\data".to_string()).unwrap();
+        assert_eq!(get_labels(&selection), vec!["Win", "Unix", "WSL"]);
+    }
+
+    #[test]
+    fn test_device_paths() {
+        let inputs = vec![
+            r"\\.\C:",
+            r"\\.\PhysicalDrive0",
+            r"\\.\pipe\pathte",
+            r"`\\.\C:`",
+        ];
+        for input in inputs {
+            assert!(PathSelection::get_initial_path(input.to_string()).is_none());
+            assert!(PathSelection::builder().build(input.to_string()).is_none());
+        }
+    }
+
+    #[test]
+    fn test_wsl_distro_option() {
+        assert_eq!(
+            get_outputs(r"\\wsl$\Ubuntu\etc\hosts"),
+            vec![
+                r"\\wsl$\Ubuntu\etc\hosts",
+                "/etc/hosts",
+                "//wsl$/Ubuntu/etc/hosts"
+            ]
+        );
+        assert_eq!(
+            get_outputs(r"\\wsl$\Ubuntu\"),
+            vec![r"\\wsl$\Ubuntu\", "/", "//wsl$/Ubuntu/"]
+        );
+    }
+
+    #[test]
+    fn test_wsl_localhost_option() {
+        assert_eq!(
+            get_outputs(r"\\wsl.localhost\Ubuntu\home\me\file"),
+            vec![
+                r"\\wsl.localhost\Ubuntu\home\me\file",
+                "/home/me/file",
+                "//wsl.localhost/Ubuntu/home/me/file"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_wsl_unc_option() {
+        let builder = PathSelection::builder().wsl_distro("Ubuntu".to_string());
+        let selection = builder.build("/home/me/file".to_string()).unwrap();
+        assert_eq!(
+            selection.options.last().unwrap().path,
+            r"\\wsl$\Ubuntu\home\me\file"
+        );
+        assert_eq!(get_labels(&selection).last().unwrap(), "Distro UNC");
+
+        // Paths on Windows drives are offered in their Windows form instead
+        let selection = builder.build("/mnt/c/Users".to_string()).unwrap();
+        assert!(!get_labels(&selection).contains(&"Distro UNC".to_string()));
+
+        // The distribution of a copied path is kept
+        let selection = builder
+            .build(r"\\wsl$\Debian\etc\hosts".to_string())
+            .unwrap();
+        assert_eq!(selection.options[0].path, r"\\wsl$\Debian\etc\hosts");
+        assert!(!get_labels(&selection).contains(&"Distro UNC".to_string()));
+    }
+
+    #[test]
+    fn test_canonical_casing() {
+        let canonical =
+            PathSelection::restore_trailing_separator(r"c:\users\", r"C:\Users".to_string());
+        assert_eq!(canonical, r"C:\Users\");
+        assert!(PathSelection::is_recased(r"c:\users\", &canonical));
+
+        assert!(!PathSelection::is_recased(r"C:\Users", r"C:\Users"));
+        assert!(!PathSelection::is_recased(r"Z:\data", r"\\nas\share\data"));
+    }
+
+    #[test]
+    fn test_diff_prefixes() {
+        let pairs = vec![
+            ("+++ b/src/main.rs", Some("src/main.rs")),
+            ("--- a/src/main.rs", Some("src/main.rs")),
+            (
+                "--- a/src/main.rs\t2024-01-01 12:00:00",
+                Some("src/main.rs"),
+            ),
+            ("--- /dev/null", Some("/dev/null")),
+            ("b/src/main.rs", Some("src/main.rs")),
+            ("src/main.rs", None),
+            ("+++b/src/main.rs", None),
+        ];
+
+        for (input, expected) in pairs {
+            assert_eq!(PathSelection::strip_diff_prefix(input), expected);
+        }
+
+        let selection = PathSelection::builder()
+            .strip_diff_prefixes(true)
+            .build("+++ b/src/main.rs".to_string())
+            .unwrap();
+        assert_eq!(selection.get_selected_path_string(), "src/main.rs");
+        assert_eq!(selection.get_original_string(), "+++ b/src/main.rs");
+    }
+
+    #[test]
+    fn test_relative_options() {
+        let builder = PathSelection::builder().relative_base(r"C:\projects\app".to_string());
+
+        let selection = builder
+            .build(r"C:\projects\lib\file.txt".to_string())
+            .unwrap();
+        assert_eq!(
+            get_labels(&selection),
+            vec!["Win", "Unix", "WSL", "Rel", "Rel/"]
+        );
+        assert_eq!(selection.options[3].path, r"..\lib\file.txt");
+        assert_eq!(selection.options[4].path, "../lib/file.txt");
+
+        // Paths on other drives have no relative path
+        let selection = builder.build(r"D:\file.txt".to_string()).unwrap();
+        assert_eq!(get_labels(&selection), vec!["Win", "Unix", "WSL"]);
+    }
+
+    #[test]
+    fn test_forward_slash_unc() {
+        assert_eq!(
+            get_outputs("//server/share/file.txt"),
+            vec!["//server/share/file.txt", r"\\server\share\file.txt"]
+        );
+        assert_eq!(
+            get_outputs(r"\\server\share\file.txt"),
+            vec![r"\\server\share\file.txt", "//server/share/file.txt"]
+        );
+
+        // Comments and bare hosts aren't UNC paths
+        assert!(PathSelection::builder()
+            .build("// Comment".to_string())
+            .is_none());
+        assert!(PathSelection::builder()
+            .build("//server".to_string())
+            .is_none());
+    }
+
+    #[test]
+    fn test_basename_option() {
+        let builder = PathSelection::builder().basename(true);
+
+        let selection = builder.build(r"C:\foo\file.txt".to_string()).unwrap();
+        assert_eq!(get_labels(&selection), vec!["Win", "Unix", "WSL", "Name"]);
+        assert_eq!(selection.options[3].path, "file.txt");
+
+        let selection = builder.build(r"C:\".to_string()).unwrap();
+        assert_eq!(get_labels(&selection), vec!["Win", "Unix", "WSL"]);
+    }
+
+    #[test]
+    fn test_promote_wsl_option() {
+        let mut options = vec![
+            PathOption::new(OptionKind::Windows, r"C:\foo".to_string()),
+            PathOption::new(OptionKind::Unix, "C:/foo".to_string()),
+            PathOption::new(OptionKind::Wsl, "/mnt/c/foo".to_string()),
+        ];
+        assert!(PathSelection::promote_wsl_option(&mut options));
+
+        let kinds: Vec<_> = options.iter().map(|x| x.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![OptionKind::Windows, OptionKind::Wsl, OptionKind::Unix]
+        );
+
+        options.retain(|x| x.kind != OptionKind::Wsl);
+        assert!(!PathSelection::promote_wsl_option(&mut options));
+
+        let builder = PathSelection::builder().prefer_wsl_for_drive_paths(true);
+        let selection = builder.build("C:/foo".to_string()).unwrap();
+        assert_eq!(selection.get_selected_path_string(), "/mnt/c/foo");
+
+        // Without a WSL option, the Unix form stays selected
+        let selection = builder
+            .enabled_options(vec![OptionKind::Windows, OptionKind::Unix])
+            .build("C:/foo".to_string())
+            .unwrap();
+        assert_eq!(selection.get_selected_path_string(), "C:/foo");
+    }
+
+    #[test]
+    fn test_makefile_escaping() {
+        let pairs = vec![
+            ("/home/user/file.txt", "/home/user/file.txt"),
+            ("/home/$USER/file.txt", "/home/$$USER/file.txt"),
+            ("/mnt/c/Program Files/app", r"/mnt/c/Program\ Files/app"),
+            ("/home/user/#notes", r"/home/user/\#notes"),
+        ];
+        for (input, expected) in pairs {
+            assert_eq!(PathSelection::escape_for_makefile(input), expected);
+        }
+
+        let builder = PathSelection::builder().makefile_escaping(true);
+        let selection = builder.build(r"C:\My Files\a".to_string()).unwrap();
+        assert_eq!(
+            get_labels(&selection),
+            vec!["Win", "Unix", "WSL", "Make", "Make WSL"]
+        );
+        assert_eq!(selection.options[4].path, r"/mnt/c/My\ Files/a");
+
+        // Paths without special characters don't need an escaped option
+        let selection = builder.build(r"C:\Files\a".to_string()).unwrap();
+        assert_eq!(get_labels(&selection), vec!["Win", "Unix", "WSL"]);
+    }
+
+    #[test]
+    fn test_dot_relative_options() {
+        assert_eq!(
+            get_outputs(r".\src\main.rs"),
+            vec![r".\src\main.rs", "./src/main.rs"]
+        );
+        assert_eq!(
+            get_outputs("./src/main.rs"),
+            vec!["./src/main.rs", r".\src\main.rs"]
+        );
+        assert_eq!(get_outputs(r"..\lib"), vec![r"..\lib", "../lib"]);
+    }
+
+    #[test]
+    fn test_bare_names() {
+        for name in ["file.txt", "file", ".hidden", "file.txt:12"] {
+            assert!(PathSelection::builder().build(name.to_string()).is_none());
+        }
+    }
+
+    #[test]
+    fn test_select_by_name() {
+        let mut selection = PathSelection::builder()
+            .build(r"C:\Users".to_string())
+            .unwrap();
+        assert!(selection.select("wsl".parse().unwrap()));
+        assert_eq!(selection.get_selected_path_string(), "/mnt/c/Users");
+        assert!(!selection.select(OptionKind::WslDistro));
+        assert_eq!(selection.get_selected_path_string(), "/mnt/c/Users");
+
+        assert_eq!("windows_short".parse(), Ok(OptionKind::WindowsShort));
+        assert!("Win".parse::<OptionKind>().is_err());
+    }
+
+    #[test]
+    fn test_home_variable_option() {
+        let builder =
+            PathSelection::builder().home_variable(VariableStyle::Posix, "/home/me/".to_string());
+        let selection = builder.build("/home/me/x/y".to_string()).unwrap();
+        assert_eq!(get_labels(&selection).last().unwrap(), "Var");
+        assert_eq!(selection.options.last().unwrap().path, "$HOME/x/y");
+        let selection = builder.build("/home/me".to_string()).unwrap();
+        assert_eq!(selection.options.last().unwrap().path, "$HOME");
+        let selection = builder.build("/home/meow/x".to_string()).unwrap();
+        assert!(!get_labels(&selection).contains(&"Var".to_string()));
+
+        let windows_home = r"C:\Users\me".to_string();
+        let builder =
+            PathSelection::builder().home_variable(VariableStyle::PowerShell, windows_home.clone());
+        let selection = builder.build(r"c:\users\me\x".to_string()).unwrap();
+        assert_eq!(
+            selection.options.last().unwrap().path,
+            r"$env:USERPROFILE\x"
+        );
+        let builder = PathSelection::builder().home_variable(VariableStyle::Cmd, windows_home);
+        let selection = builder.build("C:/Users/me/x".to_string()).unwrap();
+        assert_eq!(selection.options.last().unwrap().path, r"%USERPROFILE%\x");
+        let selection = builder.build(r"D:\Users\me\x".to_string()).unwrap();
+        assert!(!get_labels(&selection).contains(&"Var".to_string()));
+    }
+
+    #[test]
+    fn test_cygwin_option() {
+        let selection = PathSelection::builder()
+            .build("/cygdrive/c/Users".to_string())
+            .unwrap();
+        assert_eq!(selection.get_selected_path_string(), "/cygdrive/c/Users");
+        assert!(selection
+            .options
+            .iter()
+            .any(|x| x.kind == OptionKind::Windows && x.path == r"C:\Users"));
+
+        let selection = PathSelection::builder()
+            .build(r"C:\Users".to_string())
+            .unwrap();
+        assert!(!get_labels(&selection).contains(&"Cyg".to_string()));
+        let selection = PathSelection::builder()
+            .cygwin(true)
+            .build(r"C:\Users".to_string())
+            .unwrap();
+        assert!(get_labels(&selection).contains(&"Cyg".to_string()));
+    }
+
+    #[test]
+    fn test_msys_option() {
+        let selection = PathSelection::builder()
+            .build("/c/Users/me".to_string())
+            .unwrap();
+        assert_eq!(get_labels(&selection), vec!["Win", "Unix", "WSL", "MSYS"]);
+        assert_eq!(selection.get_selected_path_string(), "/c/Users/me");
+        assert_eq!(selection.options[0].path, r"C:\Users\me");
+
+        let selection = PathSelection::builder()
+            .build(r"C:\Users".to_string())
+            .unwrap();
+        assert!(!get_labels(&selection).contains(&"MSYS".to_string()));
+        let selection = PathSelection::builder()
+            .msys(true)
+            .build(r"C:\Users".to_string())
+            .unwrap();
+        assert!(get_labels(&selection).contains(&"MSYS".to_string()));
+    }
+
+    #[test]
+    fn test_spaces_and_parentheses() {
+        assert_eq!(
+            get_outputs(r"C:\Program Files (x86)\App"),
+            vec![
+                r"C:\Program Files (x86)\App",
+                "C:/Program Files (x86)/App",
+                "/mnt/c/Program Files (x86)/App"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_output_string() {
+        let builder = PathSelection::builder().output_template("`{path}`".to_string());
+        let mut selection = builder.build("<C:/foo:3>".to_string()).unwrap();
+        assert_eq!(selection.get_output_string(), "`<C:/foo:3>`");
+        selection.previous();
+        assert_eq!(selection.get_info().output, r"`<C:\foo:3>`");
+
+        let builder = builder.commit_unchanged_behavior(CommitUnchangedBehavior::LiteralOriginal);
+        let mut selection = builder.build("<C:/foo:3>".to_string()).unwrap();
+        assert!(selection.commits_original());
+        assert_eq!(selection.get_info().output, "<C:/foo:3>");
+        selection.next();
+        assert!(!selection.commits_original());
+        assert_eq!(selection.get_info().output, "`</mnt/c/foo:3>`");
+    }
+
+    #[test]
+    fn test_osc8_link_option() {
+        let builder = PathSelection::builder().osc8_link(true);
+        let selection = builder.build(r"C:\My Files\a.txt".to_string()).unwrap();
+        assert_eq!(
+            selection.options.last().unwrap().path,
+            "\x1b]8;;file:///C:/My%20Files/a.txt\x1b\\a.txt\x1b]8;;\x1b\\"
+        );
+        assert_eq!(get_labels(&selection).last().unwrap(), "Link");
+
+        let selection = builder.build("/home/me".to_string()).unwrap();
+        assert!(!get_labels(&selection).contains(&"Link".to_string()));
+        let selection = PathSelection::builder()
+            .build(r"C:\My Files".to_string())
+            .unwrap();
+        assert!(!get_labels(&selection).contains(&"Link".to_string()));
+    }
+
+    #[test]
+    fn test_file_uri_option() {
+        let selection = PathSelection::builder()
+            .build("file:///C:/My%20Documents/report.pdf".to_string())
+            .unwrap();
+        assert_eq!(
+            selection
+                .options
+                .iter()
+                .map(|x| x.path.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                r"C:\My Documents\report.pdf",
+                "C:/My Documents/report.pdf",
+                "/mnt/c/My Documents/report.pdf",
+                "file:///C:/My%20Documents/report.pdf",
+            ]
+        );
+        assert_eq!(selection.get_selected_kind(), OptionKind::FileUri);
+
+        let builder = PathSelection::builder().file_uri(true);
+        let selection = builder.build(r"\\server\share\a b".to_string()).unwrap();
+        assert_eq!(
+            selection.options.last().unwrap().path,
+            "file://server/share/a%20b"
+        );
+        let selection = builder.build("/home/me".to_string()).unwrap();
+        assert!(!get_labels(&selection).contains(&"URI".to_string()));
+    }
+
+    #[test]
+    fn test_quoted_paths() {
+        let pairs = vec![
+            (
+                r#""C:\Users\me\file.txt""#,
+                vec![
+                    r#""C:\Users\me\file.txt""#,
+                    r#""C:/Users/me/file.txt""#,
+                    r#""/mnt/c/Users/me/file.txt""#,
+                ],
+            ),
+            (
+                r#""C:\Program Files""#,
+                vec![
+                    r#""C:\Program Files""#,
+                    r#""C:/Program Files""#,
+                    r#""/mnt/c/Program Files""#,
+                ],
+            ),
+            ("'/home/user'", vec!["'/home/user'", r"'\home\user'"]),
+        ];
+
+        for (input, expected) in pairs {
+            assert_eq!(get_outputs(input), expected);
         }
+
+        let builder = PathSelection::builder().deduplicate(true);
+        let mut selection = builder.build(r#""/mnt/c/Users""#.to_string()).unwrap();
+        assert_eq!(selection.get_selected_path_string(), r#""/mnt/c/Users""#);
+        selection.next();
+        assert_eq!(selection.get_selected_path_string(), r#""\mnt\c\Users""#);
+
+        // Only a matching pair of quotes is removed
+        assert!(PathSelection::builder()
+            .build(r#""C:\Users'"#.to_string())
+            .is_none());
+    }
+
+    #[test]
+    fn test_rule_options() {
+        let rule = |pattern: &str| {
+            ConversionRule::new(String::new(), pattern, "http://files/$1".to_string()).unwrap()
+        };
+        let builder =
+            PathSelection::builder().rules(vec![rule(r"^C:\\share\\(.*)$"), rule("^/home/(.*)$")]);
+
+        let selection = builder.build(r"C:\share\a.txt".to_string()).unwrap();
+        assert_eq!(get_labels(&selection).last().unwrap(), "Rule");
+        assert_eq!(selection.options.last().unwrap().path, "http://files/a.txt");
+
+        let selection = builder.build(r"C:\other\a.txt".to_string()).unwrap();
+        assert!(!get_labels(&selection).contains(&"Rule".to_string()));
+    }
+
+    #[test]
+    fn test_expand_env_vars() {
+        std::env::set_var("PATHTE_TEST_DOCUMENTS", r"C:\Users\me\Documents");
+        let input = r"%PATHTE_TEST_DOCUMENTS%\a.txt".to_string();
+
+        let selection = PathSelection::builder()
+            .expand_env_vars(true)
+            .build(input.clone())
+            .unwrap();
+        assert_eq!(selection.options[0].path, r"C:\Users\me\Documents\a.txt");
+        assert_eq!(selection.options[2].path, "/mnt/c/Users/me/Documents/a.txt");
+        assert_eq!(selection.get_original_string(), input);
+
+        let selection = PathSelection::builder().build(input.clone()).unwrap();
+        assert_eq!(selection.options[0].path, input);
+    }
+
+    #[test]
+    fn test_normalize_paths() {
+        let builder = PathSelection::builder().normalize_paths(true);
+        let selection = builder
+            .build("/mnt/c//Users/./me/../test".to_string())
+            .unwrap();
+        assert!(selection
+            .options
+            .iter()
+            .any(|x| x.path == "/mnt/c/Users/test"));
+        assert!(!selection.options.iter().any(|x| x.path.contains("..")));
+
+        // Without normalizing, `//` isn't accepted in Unix paths
+        assert!(PathSelection::builder()
+            .build("/home//user".to_string())
+            .is_none());
+        let selection = builder.build("/home//user".to_string()).unwrap();
+        assert_eq!(selection.get_selected_path_string(), "/home/user");
+    }
+
+    #[test]
+    fn test_forward_slash_windows_paths() {
+        // The Unix option of a Windows path is the same path with forward slashes, e.g. for YAML or
+        // JSON files, so there is no separate option for it
+        for input in [
+            r"C:\foo\bar",
+            r"C:\Program Files (x86)\App\",
+            r"D:\",
+            r"foo\bar.txt",
+        ] {
+            let mut selection = PathSelection::builder().build(input.to_string()).unwrap();
+            selection.next();
+            assert_eq!(get_labels(&selection)[selection.current], "Unix");
+            assert_eq!(
+                selection.get_selected_path_string(),
+                input.replace('\\', "/")
+            );
+        }
+    }
+
+    #[test]
+    fn test_glob_patterns() {
+        let builder = PathSelection::builder().glob_patterns(true);
+
+        let selection = builder.build(r"C:\logs\*.txt".to_string()).unwrap();
+        assert_eq!(get_labels(&selection), vec!["Win", "Unix", "WSL"]);
+        assert_eq!(selection.options[1].path, "C:/logs/*.txt");
+        assert_eq!(selection.options[2].path, "/mnt/c/logs/*.txt");
+
+        let selection = builder.build("/var/log/**/*.log".to_string()).unwrap();
+        assert_eq!(selection.options[0].path, r"\var\log\**\*.log");
+        assert_eq!(selection.options[1].path, "/var/log/**/*.log");
+
+        let selection = builder.build("/mnt/c/logs/app-?.log".to_string()).unwrap();
+        assert_eq!(selection.options[0].path, r"\mnt\c\logs\app-?.log");
+        assert_eq!(selection.options[2].path, "/mnt/c/logs/app-?.log");
+
+        let selection = builder.build(r"\\server\share\**\*".to_string()).unwrap();
+        assert_eq!(selection.options[1].path, "//server/share/**/*");
+
+        assert!(PathSelection::builder()
+            .build(r"C:\logs\*.txt".to_string())
+            .is_none());
+    }
+
+    #[test]
+    fn test_multi_line_lines() {
+        let builder = PathSelection::builder().multi_line_mode(MultiLineMode::Lines);
+
+        let mut selection = builder
+            .build("C:\\My Files\\a.txt\n/home/me/b.txt\n".to_string())
+            .unwrap();
+        // All lines are converted to the same format, starting with the one of the first path.
+        // `/home/me` has no WSL form, so there is no WSL option for the block.
+        assert_eq!(get_labels(&selection), vec!["Win", "Unix"]);
+        assert_eq!(
+            selection.get_selected_path_string(),
+            "C:\\My Files\\a.txt\n\\home\\me\\b.txt\n"
+        );
+        selection.next();
+        assert_eq!(
+            selection.get_selected_path_string(),
+            "C:/My Files/a.txt\n/home/me/b.txt\n"
+        );
+
+        // Single paths are converted as before
+        let selection = builder.build(r"C:\Users".to_string()).unwrap();
+        assert_eq!(get_labels(&selection), vec!["Win", "Unix", "WSL"]);
+    }
+
+    #[test]
+    fn test_select_index() {
+        let mut selection = PathSelection::builder()
+            .commit_unchanged_behavior(CommitUnchangedBehavior::LiteralOriginal)
+            .build(r"C:\Users".to_string())
+            .unwrap();
+        assert!(selection.commits_original());
+
+        assert!(selection.select_index(2));
+        assert_eq!(selection.get_selected_path_string(), "/mnt/c/Users");
+        assert!(!selection.commits_original());
+
+        assert!(!selection.select_index(3));
+        assert_eq!(selection.get_selected_path_string(), "/mnt/c/Users");
+
+        // Selecting by kind commits the option as well, even if it is the path's own format
+        let mut selection = PathSelection::builder()
+            .commit_unchanged_behavior(CommitUnchangedBehavior::LiteralOriginal)
+            .build("`C:\\Users`".to_string())
+            .unwrap();
+        assert_eq!(selection.get_output_string(), "`C:\\Users`");
+        assert!(selection.select(OptionKind::Windows));
+        assert!(!selection.commits_original());
+        assert_eq!(selection.get_output_string(), "`C:\\Users`");
+        assert!(selection.select(OptionKind::Unix));
+        assert_eq!(selection.get_output_string(), "`C:/Users`");
+    }
+
+    #[test]
+    fn test_start_at_last() {
+        let builder = PathSelection::builder()
+            .commit_unchanged_behavior(CommitUnchangedBehavior::LiteralOriginal)
+            .start_at_last(true);
+
+        let mut selection = builder.build(r"C:\Users".to_string()).unwrap();
+        assert_eq!(selection.get_selected_path_string(), "/mnt/c/Users");
+        assert!(!selection.commits_original());
+
+        // Cycling on continues backwards from the last option
+        selection.previous();
+        assert_eq!(selection.get_selected_path_string(), "C:/Users");
+    }
+
+    #[test]
+    fn test_reordered_and_disabled_formats() {
+        let builder = PathSelection::builder()
+            .option_priority(vec![OptionKind::Wsl, OptionKind::Windows])
+            .enabled_options(vec![OptionKind::Windows, OptionKind::Wsl]);
+        let selection = builder.build(r"C:\Users".to_string()).unwrap();
+        assert_eq!(get_labels(&selection), vec!["WSL", "Win"]);
+        assert_eq!(selection.get_selected_path_string(), r"C:\Users");
+
+        // `/home/user` has no WSL form, so only its Windows form would be left to select
+        assert!(builder.build("/home/user".to_string()).is_none());
+    }
+
+    #[test]
+    fn test_last_option() {
+        let builder = PathSelection::builder()
+            .initial_option(OptionKind::Unix)
+            .last_option(OptionKind::Wsl);
+        let selection = builder.build(r"C:\Users\test".to_string()).unwrap();
+        assert_eq!(selection.get_selected_kind(), OptionKind::Wsl);
+        assert_eq!(selection.get_selected_path_string(), "/mnt/c/Users/test");
+
+        // Without a WSL form, the initial option is selected instead
+        let selection = builder.build(r"\\server\share".to_string()).unwrap();
+        assert_eq!(selection.get_selected_kind(), OptionKind::Windows);
+
+        let selection = PathSelection::builder()
+            .last_option(OptionKind::Wsl)
+            .build("/home/user".to_string())
+            .unwrap();
+        assert_eq!(selection.get_selected_kind(), OptionKind::Unix);
+
+        // A path that is already in the last pasted format keeps it selected
+        let selection = PathSelection::builder()
+            .initial_option(OptionKind::Unix)
+            .last_option(OptionKind::Windows)
+            .build(r"C:\b".to_string())
+            .unwrap();
+        assert_eq!(selection.get_selected_kind(), OptionKind::Windows);
+        assert_eq!(selection.get_selected_path_string(), r"C:\b");
+    }
+
+    #[test]
+    fn test_quote_spaces() {
+        let builder = PathSelection::builder()
+            .quote_spaces(true)
+            .commit_unchanged_behavior(CommitUnchangedBehavior::LiteralOriginal);
+        let mut selection = builder.build(r"C:\My Files\a.txt".to_string()).unwrap();
+        selection.select(OptionKind::Windows);
+        assert_eq!(
+            selection.get_selected_path_string(),
+            r#""C:\My Files\a.txt""#
+        );
+        assert_eq!(selection.get_search_string(), r"C:\My Files\a.txt");
+        selection.select(OptionKind::Wsl);
+        assert_eq!(
+            selection.get_selected_path_string(),
+            "'/mnt/c/My Files/a.txt'"
+        );
+
+        let mut selection = builder.build("/home/me/it's here".to_string()).unwrap();
+        selection.select(OptionKind::Unix);
+        assert_eq!(
+            selection.get_selected_path_string(),
+            r"'/home/me/it'\''s here'"
+        );
+
+        // Paths without spaces and quoted paths aren't quoted again
+        let mut selection = builder.build(r"C:\Files".to_string()).unwrap();
+        selection.select(OptionKind::Windows);
+        assert_eq!(selection.get_selected_path_string(), r"C:\Files");
+        let mut selection = builder.build(r#""C:\My Files""#.to_string()).unwrap();
+        selection.select(OptionKind::Wsl);
+        assert_eq!(selection.get_selected_path_string(), r#""/mnt/c/My Files""#);
     }
 }
@@ -0,0 +1,45 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "gui")]
+use crate::EGUI_CONTEXT;
+use crate::{clipboard, keyboard_hook};
+
+pub const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(1);
+
+static QUIT_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Removes the keyboard hook, waits for background threads and then asks the GUI (or the message
+/// loop without it) to close.
+pub fn quit() {
+    keyboard_hook::stop_keyboard_hook_thread(SHUTDOWN_TIMEOUT);
+    clipboard::join_restore_threads(SHUTDOWN_TIMEOUT);
+
+    QUIT_REQUESTED.store(true, Ordering::SeqCst);
+
+    // The GUI may be idle in reactive mode, so it has to be woken up to notice the request
+    #[cfg(feature = "gui")]
+    if let Some(ctx) = EGUI_CONTEXT.lock().unwrap().as_ref() {
+        ctx.request_repaint();
+    }
+}
+
+pub fn is_quit_requested() -> bool {
+    QUIT_REQUESTED.load(Ordering::SeqCst)
+}
+
+/// Joins the given thread unless it is still running after `timeout`.
+/// Returns whether the thread has finished.
+pub fn join_with_timeout(handle: JoinHandle<()>, timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+    while !handle.is_finished() {
+        if Instant::now() >= deadline {
+            return false;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    let _ = handle.join();
+    true
+}
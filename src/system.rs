@@ -0,0 +1,162 @@
+//! Lookups in the file system and network of Windows that some path options need. On other platforms
+//! they fail, so these options aren't offered.
+
+pub use imp::{
+    get_final_path_name, get_network_drive_mappings, get_network_drive_target, get_short_path_name,
+    log_debug_message,
+};
+
+#[cfg(windows)]
+mod imp {
+    use windows::core::{PCWSTR, PWSTR};
+    use windows::Win32::Foundation::{CloseHandle, ERROR_MORE_DATA, HANDLE};
+    use windows::Win32::NetworkManagement::WNet::WNetGetConnectionW;
+    use windows::Win32::Storage::FileSystem::{
+        CreateFileW, GetFinalPathNameByHandleW, GetLogicalDrives, GetShortPathNameW,
+        FILE_FLAG_BACKUP_SEMANTICS, FILE_NAME_NORMALIZED, FILE_SHARE_DELETE, FILE_SHARE_READ,
+        FILE_SHARE_WRITE, OPEN_EXISTING,
+    };
+    use windows::Win32::System::Diagnostics::Debug::OutputDebugStringW;
+
+    use crate::APP_NAME;
+
+    /// Logs the message for debuggers and tools like DebugView, since there is no console.
+    pub fn log_debug_message(message: &str) {
+        let message = to_wide_null(&format!("{APP_NAME}: {message}\n"));
+        unsafe {
+            OutputDebugStringW(PCWSTR(message.as_ptr()));
+        }
+    }
+
+    pub fn get_short_path_name(path: &str) -> Result<String, String> {
+        let long_path = to_wide_null(path);
+
+        unsafe {
+            // The first call only determines the required buffer size (including the terminator)
+            let required_length = GetShortPathNameW(PCWSTR::from_raw(long_path.as_ptr()), None);
+            if required_length == 0 {
+                return Err("Failed to get short path name.".to_string());
+            }
+
+            let mut buffer = vec![0u16; required_length as usize];
+            let length = GetShortPathNameW(PCWSTR::from_raw(long_path.as_ptr()), Some(&mut buffer));
+            if length == 0 || length as usize >= buffer.len() {
+                return Err("Failed to get short path name.".to_string());
+            }
+
+            Ok(String::from_utf16_lossy(&buffer[..length as usize]))
+        }
+    }
+
+    /// Returns the path of an existing file or folder as it is stored on disk, e.g. with its actual casing.
+    /// Links are resolved, so the result may point to a different location.
+    pub fn get_final_path_name(path: &str) -> Result<String, String> {
+        let file_name = to_wide_null(path);
+
+        unsafe {
+            // Opening without access rights only queries metadata, and the backup semantics allow opening folders
+            let handle = CreateFileW(
+                PCWSTR::from_raw(file_name.as_ptr()),
+                0,
+                FILE_SHARE_READ | FILE_SHARE_WRITE | FILE_SHARE_DELETE,
+                None,
+                OPEN_EXISTING,
+                FILE_FLAG_BACKUP_SEMANTICS,
+                HANDLE(0),
+            )
+            .map_err(|e| e.to_string())?;
+
+            let mut buffer = vec![0u16; 1024];
+            let mut length = GetFinalPathNameByHandleW(handle, &mut buffer, FILE_NAME_NORMALIZED);
+            if length as usize > buffer.len() {
+                // The required length (including the terminator) has been returned
+                buffer.resize(length as usize, 0);
+                length = GetFinalPathNameByHandleW(handle, &mut buffer, FILE_NAME_NORMALIZED);
+            }
+            CloseHandle(handle);
+
+            if length == 0 || length as usize > buffer.len() {
+                return Err("Failed to get final path name.".to_string());
+            }
+
+            let final_path = String::from_utf16_lossy(&buffer[..length as usize]);
+            Ok(match final_path.strip_prefix(r"\\?\UNC\") {
+                Some(unc_path) => format!(r"\\{}", unc_path),
+                None => final_path.trim_start_matches(r"\\?\").to_string(),
+            })
+        }
+    }
+
+    /// Returns all drive letters that are mapped to a network share, along with their UNC targets.
+    pub fn get_network_drive_mappings() -> Vec<(char, String)> {
+        let drives = unsafe { GetLogicalDrives() };
+
+        ('A'..='Z')
+            .enumerate()
+            .filter(|(index, _)| drives & (1 << index) != 0)
+            .filter_map(|(_, drive_letter)| {
+                get_network_drive_target(drive_letter)
+                    .ok()
+                    .map(|target| (drive_letter, target))
+            })
+            .collect()
+    }
+
+    /// Returns the UNC path (e.g. `\\server\share`) a network drive letter is mapped to.
+    pub fn get_network_drive_target(drive_letter: char) -> Result<String, String> {
+        let local_name = to_wide_null(&format!("{}:", drive_letter));
+        let mut buffer = vec![0u16; 260];
+
+        loop {
+            let mut length = buffer.len() as u32;
+            let result = unsafe {
+                WNetGetConnectionW(
+                    PCWSTR::from_raw(local_name.as_ptr()),
+                    PWSTR::from_raw(buffer.as_mut_ptr()),
+                    &mut length,
+                )
+            };
+
+            match result {
+                0 => {
+                    let end = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+                    return Ok(String::from_utf16_lossy(&buffer[..end]));
+                }
+                // The required length has been written to `length`
+                code if code == ERROR_MORE_DATA.0 && length as usize > buffer.len() => {
+                    buffer.resize(length as usize, 0);
+                }
+                _ => return Err("The drive is not a network drive.".to_string()),
+            }
+        }
+    }
+
+    fn to_wide_null(text: &str) -> Vec<u16> {
+        text.encode_utf16().chain(std::iter::once(0)).collect()
+    }
+}
+
+#[cfg(not(windows))]
+mod imp {
+    use crate::APP_NAME;
+
+    pub fn log_debug_message(message: &str) {
+        eprintln!("{APP_NAME}: {message}");
+    }
+
+    pub fn get_short_path_name(_path: &str) -> Result<String, String> {
+        Err("Short path names are only available on Windows.".to_string())
+    }
+
+    pub fn get_final_path_name(_path: &str) -> Result<String, String> {
+        Err("Final path names are only available on Windows.".to_string())
+    }
+
+    pub fn get_network_drive_mappings() -> Vec<(char, String)> {
+        Vec::new()
+    }
+
+    pub fn get_network_drive_target(_drive_letter: char) -> Result<String, String> {
+        Err("Network drives are only available on Windows.".to_string())
+    }
+}
@@ -1,7 +1,33 @@
 use image::load_from_memory;
-use std::process;
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::thread;
-use tray_icon::{menu::Menu, menu::MenuEvent, menu::MenuItem, Icon, TrayIcon, TrayIconBuilder};
+use tray_icon::menu::{
+    AboutMetadata, CheckMenuItem, Menu, MenuEvent, MenuItem, PredefinedMenuItem, Submenu,
+};
+use tray_icon::{Icon, TrayIcon, TrayIconBuilder};
+
+#[cfg(feature = "gui")]
+use crate::dialog::{self, ListSelectionDialog, RuleTesterDialog};
+use crate::{active_config, clipboard, keyboard_hook, path, set_active_profile, shutdown, win_api};
+use crate::{APP_NAME, BASE_CONFIG, RELATIVE_BASE};
+
+thread_local! {
+    // Menu items aren't Send, so they stay on the thread that created the tray icon
+    static PROFILE_ITEMS: RefCell<Vec<(Option<String>, CheckMenuItem)>> = const { RefCell::new(Vec::new()) };
+    static AUTOSTART_ITEM: RefCell<Option<CheckMenuItem>> = const { RefCell::new(None) };
+}
+
+static PROFILE_CHANGED: AtomicBool = AtomicBool::new(true);
+static AUTOSTART_CHANGED: AtomicBool = AtomicBool::new(false);
+// Read by the keyboard hook when a selection is committed
+static COPY_ONLY: AtomicBool = AtomicBool::new(false);
+
+#[cfg(feature = "gui")]
+const PICKER_LABEL: &str = "Convert clipboard...";
+// Without the GUI, there is no dialog to pick from, so the path is converted right away
+#[cfg(not(feature = "gui"))]
+const PICKER_LABEL: &str = "Convert clipboard";
 
 fn create_icon() -> Icon {
     const ICON_BYTES: &[u8] = include_bytes!("..\\resources\\icon.ico");
@@ -14,9 +40,131 @@ fn create_icon() -> Icon {
     Icon::from_rgba(icon.into_raw(), width, height).expect("Failed to create icon.")
 }
 
+/// Stores the Windows form of the copied path, or clears the base if the clipboard has no path.
+fn set_relative_base_from_clipboard() {
+    let relative_base = clipboard::get_clipboard_path(active_config().keep_shortcuts)
+        .ok()
+        .and_then(|text| path::detect_path(text.trim()))
+        .and_then(|path| path.to_windows().ok())
+        .map(|path| path.as_string());
+
+    *RELATIVE_BASE.lock().unwrap() = relative_base;
+}
+
+#[cfg(feature = "gui")]
+fn open_picker() {
+    if let Some(picker) = ListSelectionDialog::from_clipboard(&active_config()) {
+        dialog::open(picker);
+    }
+}
+
+#[cfg(not(feature = "gui"))]
+fn open_picker() {
+    crate::convert_clipboard();
+}
+
+/// Whether committed paths are only copied to the clipboard instead of being pasted.
+pub fn is_copy_only() -> bool {
+    COPY_ONLY.load(Ordering::SeqCst)
+}
+
+pub fn mark_profile_changed() {
+    PROFILE_CHANGED.store(true, Ordering::SeqCst);
+}
+
+/// Moves the check mark to the active profile after it has changed, and updates the autostart check
+/// mark after it was toggled. Needs to be called on the thread that created the tray icon.
+pub fn update_checks(active_profile: Option<&str>) {
+    if AUTOSTART_CHANGED.swap(false, Ordering::SeqCst) {
+        // The registry is checked again, so the check mark is correct even if toggling failed
+        AUTOSTART_ITEM.with_borrow(|item| {
+            if let Some(item) = item {
+                item.set_checked(win_api::is_autostart_enabled());
+            }
+        });
+    }
+
+    if !PROFILE_CHANGED.swap(false, Ordering::SeqCst) {
+        return;
+    }
+
+    PROFILE_ITEMS.with_borrow(|items| {
+        for (name, item) in items {
+            item.set_checked(name.as_deref() == active_profile);
+        }
+    });
+}
+
+/// Registers Pathte to start at login, or removes the registration if it is registered already.
+fn toggle_autostart() {
+    if let Err(e) = win_api::set_autostart(!win_api::is_autostart_enabled()) {
+        crate::report_error(e);
+    }
+    AUTOSTART_CHANGED.store(true, Ordering::SeqCst);
+}
+
+/// Shows the version and where Pathte comes from. The menu opens the dialog by itself.
+fn create_about_item() -> PredefinedMenuItem {
+    let metadata = AboutMetadata {
+        name: Some(APP_NAME.to_string()),
+        version: Some(env!("CARGO_PKG_VERSION").to_string()),
+        comments: Some(env!("CARGO_PKG_DESCRIPTION").to_string()),
+        license: Some(env!("CARGO_PKG_LICENSE").to_string()),
+        website: Some(env!("CARGO_PKG_REPOSITORY").to_string()),
+        ..Default::default()
+    };
+    PredefinedMenuItem::about(Some(&format!("About {APP_NAME}")), Some(metadata))
+}
+
+fn create_profiles_menu() -> Submenu {
+    let profiles = Submenu::new("Profiles", !BASE_CONFIG.profiles.is_empty());
+
+    let names = std::iter::once(None).chain(BASE_CONFIG.profiles.keys().cloned().map(Some));
+    let items = names
+        .map(|name| {
+            let label = name.as_deref().unwrap_or("Default");
+            let item = CheckMenuItem::new(label, true, name.is_none(), None);
+            profiles.append(&item).expect("Failed to append menu item.");
+            (name, item)
+        })
+        .collect();
+    PROFILE_ITEMS.set(items);
+
+    profiles
+}
+
 pub fn create_tray_icon() -> TrayIcon {
+    let enabled = CheckMenuItem::new("Enabled", true, true, None);
+    let copy_only = CheckMenuItem::new("Copy instead of pasting", true, false, None);
+    let picker = MenuItem::new(PICKER_LABEL, true, None);
+    let relative_base = MenuItem::new("Set clipboard as relative base", true, None);
+    #[cfg(feature = "gui")]
+    let rule_tester = MenuItem::new("Test conversion rules...", true, None);
+    let profiles = create_profiles_menu();
+    let autostart = CheckMenuItem::new(
+        "Start with Windows",
+        true,
+        win_api::is_autostart_enabled(),
+        None,
+    );
+    let about = create_about_item();
     let quit = MenuItem::new("Quit", true, None);
     let menu = Menu::new();
+    menu.append(&enabled).expect("Failed to append menu item.");
+    menu.append(&copy_only)
+        .expect("Failed to append menu item.");
+    menu.append(&picker).expect("Failed to append menu item.");
+    menu.append(&relative_base)
+        .expect("Failed to append menu item.");
+    #[cfg(feature = "gui")]
+    menu.append(&rule_tester)
+        .expect("Failed to append menu item.");
+    menu.append(&profiles).expect("Failed to append menu item.");
+    menu.append(&autostart)
+        .expect("Failed to append menu item.");
+    menu.append(&PredefinedMenuItem::separator())
+        .expect("Failed to append menu item.");
+    menu.append(&about).expect("Failed to append menu item.");
     menu.append(&quit).expect("Failed to append menu item.");
 
     let icon = create_icon();
@@ -28,9 +176,47 @@ pub fn create_tray_icon() -> TrayIcon {
         .build()
         .unwrap();
 
+    let enabled_id = enabled.id().clone();
+    let copy_only_id = copy_only.id().clone();
+    let picker_id = picker.id().clone();
+    let relative_base_id = relative_base.id().clone();
+    #[cfg(feature = "gui")]
+    let rule_tester_id = rule_tester.id().clone();
+    let autostart_id = autostart.id().clone();
+    AUTOSTART_ITEM.set(Some(autostart));
+    let quit_id = quit.id().clone();
+    let profile_ids: Vec<_> = PROFILE_ITEMS.with_borrow(|items| {
+        items
+            .iter()
+            .map(|(name, item)| (item.id().clone(), name.clone()))
+            .collect()
+    });
     thread::spawn(move || {
-        if MenuEvent::receiver().recv().is_ok() {
-            process::exit(0);
+        while let Ok(event) = MenuEvent::receiver().recv() {
+            #[cfg(feature = "gui")]
+            if event.id == rule_tester_id {
+                dialog::open(RuleTesterDialog::new());
+                continue;
+            }
+
+            if event.id == enabled_id {
+                // The menu toggles the check mark by itself
+                keyboard_hook::toggle_enabled();
+            } else if event.id == copy_only_id {
+                COPY_ONLY.fetch_xor(true, Ordering::SeqCst);
+            } else if event.id == picker_id {
+                open_picker();
+            } else if event.id == relative_base_id {
+                set_relative_base_from_clipboard();
+            } else if let Some((_, name)) = profile_ids.iter().find(|(id, _)| *id == event.id) {
+                set_active_profile(name.clone());
+            } else if event.id == autostart_id {
+                toggle_autostart();
+            } else if event.id == quit_id {
+                // Removes the keyboard hook before the GUI exits, so that it isn't left registered
+                shutdown::quit();
+                break;
+            }
         }
     });
 
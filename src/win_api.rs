@@ -1,48 +1,593 @@
-use windows::core::PCWSTR;
-use windows::Win32::Foundation::{HWND, POINT};
+use lazy_static::lazy_static;
+use std::env;
+use std::sync::Mutex;
+use windows::core::{ComInterface, PCWSTR, PWSTR};
+use windows::Win32::Foundation::{
+    CloseHandle, BOOL, ERROR_FILE_NOT_FOUND, ERROR_SUCCESS, HMODULE, HWND, LPARAM, LRESULT,
+    MAX_PATH, POINT, RECT, WPARAM,
+};
+use windows::Win32::Graphics::Gdi::{
+    ClientToScreen, GetMonitorInfoW, MonitorFromPoint, MONITORINFO, MONITOR_DEFAULTTONEAREST,
+};
+use windows::Win32::System::Com::{
+    CoCreateInstance, CoInitializeEx, CoUninitialize, IPersistFile, CLSCTX_INPROC_SERVER,
+    COINIT_APARTMENTTHREADED, STGM_READ,
+};
+use windows::Win32::System::Diagnostics::Debug::MessageBeep;
+use windows::Win32::System::LibraryLoader::GetModuleHandleW;
+use windows::Win32::System::Registry::{
+    RegCloseKey, RegDeleteValueW, RegGetValueW, RegOpenKeyExW, RegSetValueExW, HKEY,
+    HKEY_CURRENT_USER, KEY_SET_VALUE, REG_SZ, RRF_RT_REG_SZ,
+};
+use windows::Win32::System::Threading::{
+    GetCurrentProcessId, OpenProcess, QueryFullProcessImageNameW, PROCESS_NAME_WIN32,
+    PROCESS_QUERY_LIMITED_INFORMATION,
+};
+use windows::Win32::UI::Accessibility::{SetWinEventHook, HWINEVENTHOOK};
+use windows::Win32::UI::HiDpi::{GetDpiForMonitor, MDT_EFFECTIVE_DPI};
+use windows::Win32::UI::Shell::{IShellLinkW, ShellExecuteW, ShellLink};
 use windows::Win32::UI::WindowsAndMessaging::{
-    FindWindowW, GetCursorPos, SetWindowPos, HWND_TOPMOST, SWP_NOSIZE, SWP_NOZORDER,
+    CreateWindowExW, DefWindowProcW, EnumWindows, FindWindowW, GetClassNameW, GetCursorPos,
+    GetForegroundWindow, GetGUIThreadInfo, GetSystemMetrics, GetWindowLongW,
+    GetWindowThreadProcessId, RegisterClassW, SetWindowDisplayAffinity, SetWindowLongW,
+    SetWindowPos, EVENT_SYSTEM_FOREGROUND, GUITHREADINFO, GWL_EXSTYLE, HMENU, HWND_TOPMOST, MB_OK,
+    SM_CXSCREEN, SM_CXVIRTUALSCREEN, SM_CYSCREEN, SM_CYVIRTUALSCREEN, SM_XVIRTUALSCREEN,
+    SM_YVIRTUALSCREEN, SWP_NOSIZE, SWP_NOZORDER, SW_SHOWNORMAL, WDA_EXCLUDEFROMCAPTURE,
+    WINDOW_EX_STYLE, WINDOW_STYLE, WINEVENT_OUTOFCONTEXT, WINEVENT_SKIPOWNPROCESS,
+    WM_DISPLAYCHANGE, WNDCLASSW, WS_EX_NOACTIVATE, WS_EX_TOPMOST,
 };
+#[cfg(not(feature = "gui"))]
+use windows::Win32::UI::WindowsAndMessaging::{
+    DispatchMessageW, PeekMessageW, TranslateMessage, MSG, PM_REMOVE,
+};
+#[cfg(feature = "gui")]
+use windows::Win32::UI::WindowsAndMessaging::{SWP_NOACTIVATE, SWP_NOMOVE};
 
+use crate::config::OverlayFallback;
 use crate::APP_NAME;
 
+pub use pathte::system::log_debug_message;
+
+pub type DisplayChangeHandler = fn();
+pub type ForegroundChangeHandler = fn();
+
+lazy_static! {
+    static ref DISPLAY_CHANGE_HANDLER: Mutex<Option<DisplayChangeHandler>> = Mutex::new(None);
+    static ref FOREGROUND_CHANGE_HANDLER: Mutex<Option<ForegroundChangeHandler>> = Mutex::new(None);
+    static ref LAST_CURSOR_POS: Mutex<Option<POINT>> = Mutex::new(None);
+}
+
+static DISPLAY_CHANGE_WINDOW_CLASS: &str = "PathteDisplayChange";
+static WINIT_WINDOW_CLASS: &str = "Window Class";
+// DPI at a scale of 100%
+const USER_DEFAULT_SCREEN_DPI: u32 = 96;
+// Programs started at login, where Pathte adds a value named after itself
+static RUN_KEY: &str = r"Software\Microsoft\Windows\CurrentVersion\Run";
+
 pub fn find_app_window() -> Result<HWND, String> {
+    // The buffer has to outlive the call, so it can't be a temporary
+    let window_name = to_wide_null(APP_NAME);
+    let hwnd = unsafe { FindWindowW(None, PCWSTR::from_raw(window_name.as_ptr())) };
+
+    if hwnd.0 != 0 {
+        return Ok(hwnd);
+    }
+
+    find_app_window_by_class().ok_or("Failed to find window.".to_string())
+}
+
+/// Looks for the overlay among the top-level windows of this process, identified by winit's window class
+/// and the always-on-top style. Dialogs share the class, but are not topmost.
+fn find_app_window_by_class() -> Option<HWND> {
+    let mut found = HWND(0);
     unsafe {
-        let hwnd = FindWindowW(
-            None,
-            PCWSTR::from_raw(
-                format!("{}\0", APP_NAME)
-                    .encode_utf16()
-                    .collect::<Vec<u16>>()
-                    .as_ptr(),
-            ),
+        EnumWindows(
+            Some(find_app_window_proc),
+            LPARAM(&mut found as *mut HWND as isize),
         );
+    }
 
-        if hwnd.0 == 0 {
-            Err("Failed to find window.".to_string())
-        } else {
-            Ok(hwnd)
-        }
+    (found.0 != 0).then_some(found)
+}
+
+unsafe extern "system" fn find_app_window_proc(hwnd: HWND, l_param: LPARAM) -> BOOL {
+    let mut process_id = 0;
+    GetWindowThreadProcessId(hwnd, Some(&mut process_id));
+
+    let mut class_name = [0u16; 256];
+    let length = GetClassNameW(hwnd, &mut class_name).max(0) as usize;
+    let ex_style = GetWindowLongW(hwnd, GWL_EXSTYLE) as u32;
+
+    if process_id == GetCurrentProcessId()
+        && String::from_utf16_lossy(&class_name[..length]) == WINIT_WINDOW_CLASS
+        && ex_style & WS_EX_TOPMOST.0 != 0
+    {
+        *(l_param.0 as *mut HWND) = hwnd;
+        // Stops the enumeration
+        return BOOL(0);
     }
+
+    BOOL(1)
 }
 
-pub fn move_window_to_cursor(hwnd: HWND) -> Result<(), String> {
+/// Moves the window just below the text caret of the focused window. Many applications (e.g. browsers)
+/// draw their own caret without reporting it, in which case the window is moved to the cursor instead.
+/// `content_extent` is the logical size of the visible content from the window's top left corner,
+/// which is kept inside the monitor.
+pub fn move_window_to_caret(
+    hwnd: HWND,
+    fallback: OverlayFallback,
+    content_extent: (f32, f32),
+) -> Result<(), String> {
+    match get_caret_position() {
+        Some(caret_pos) => move_window(hwnd, caret_pos, content_extent),
+        None => move_window_to_cursor(hwnd, fallback, content_extent),
+    }
+}
+
+/// Returns the bottom left corner of the caret in the foreground window in screen coordinates.
+fn get_caret_position() -> Option<POINT> {
     unsafe {
-        let mut cursor_pos = POINT::default();
-        if !GetCursorPos(&mut cursor_pos).as_bool() {
-            return Err("Failed to get cursor position.".to_string());
+        let thread_id = GetWindowThreadProcessId(GetForegroundWindow(), None);
+        let mut info = GUITHREADINFO {
+            cbSize: std::mem::size_of::<GUITHREADINFO>() as u32,
+            ..Default::default()
+        };
+        if thread_id == 0
+            || !GetGUIThreadInfo(thread_id, &mut info).as_bool()
+            || info.hwndCaret.0 == 0
+        {
+            return None;
         }
 
+        // The caret rectangle is relative to the client area of the window that owns it
+        let mut caret_pos = POINT {
+            x: info.rcCaret.left,
+            y: info.rcCaret.bottom,
+        };
+        ClientToScreen(info.hwndCaret, &mut caret_pos)
+            .as_bool()
+            .then_some(caret_pos)
+    }
+}
+
+/// Moves the window to the cursor. If the cursor position is unavailable (e.g. on the secure desktop),
+/// the window is moved to the given fallback position instead, so it never stays off-screen.
+pub fn move_window_to_cursor(
+    hwnd: HWND,
+    fallback: OverlayFallback,
+    content_extent: (f32, f32),
+) -> Result<(), String> {
+    let mut cursor_pos = POINT::default();
+    let cursor_pos = if unsafe { GetCursorPos(&mut cursor_pos) }.as_bool() {
+        *LAST_CURSOR_POS.lock().unwrap() = Some(cursor_pos);
+        cursor_pos
+    } else {
+        get_fallback_position(fallback)
+    };
+
+    move_window(hwnd, cursor_pos, content_extent)
+}
+
+fn move_window(hwnd: HWND, position: POINT, content_extent: (f32, f32)) -> Result<(), String> {
+    // The monitor layout may have changed since the last activation, so the bounds are queried every time
+    let position = clamp_to_monitor(clamp_to_virtual_screen(position), content_extent);
+
+    let moved = unsafe {
         SetWindowPos(
             hwnd,
             HWND_TOPMOST,
-            cursor_pos.x,
-            cursor_pos.y,
+            position.x,
+            position.y,
             0,
             0,
             SWP_NOSIZE | SWP_NOZORDER,
+        )
+    };
+
+    if moved.as_bool() {
+        Ok(())
+    } else {
+        Err("Failed to move window.".to_string())
+    }
+}
+
+fn get_fallback_position(fallback: OverlayFallback) -> POINT {
+    let last_cursor_pos = *LAST_CURSOR_POS.lock().unwrap();
+    match (fallback, last_cursor_pos) {
+        (OverlayFallback::LastCursor, Some(last_cursor_pos)) => last_cursor_pos,
+        (OverlayFallback::Center, _) => unsafe {
+            POINT {
+                x: GetSystemMetrics(SM_CXSCREEN) / 2,
+                y: GetSystemMetrics(SM_CYSCREEN) / 2,
+            }
+        },
+        // The primary monitor's top left corner is always at the origin
+        _ => POINT { x: 0, y: 0 },
+    }
+}
+
+/// Moves the window back above other topmost windows, e.g. borderless fullscreen apps, without
+/// activating it.
+#[cfg(feature = "gui")]
+pub fn reassert_topmost(hwnd: HWND) -> Result<(), String> {
+    let result = unsafe {
+        SetWindowPos(
+            hwnd,
+            HWND_TOPMOST,
+            0,
+            0,
+            0,
+            0,
+            SWP_NOMOVE | SWP_NOSIZE | SWP_NOACTIVATE,
+        )
+    };
+
+    if result.as_bool() {
+        Ok(())
+    } else {
+        Err("Failed to reassert topmost.".to_string())
+    }
+}
+
+/// Keeps the window from being activated when it is clicked.
+pub fn set_no_activate(hwnd: HWND) -> Result<(), String> {
+    unsafe {
+        let ex_style = GetWindowLongW(hwnd, GWL_EXSTYLE) as u32;
+        if ex_style & WS_EX_NOACTIVATE.0 != 0 {
+            return Ok(());
+        }
+
+        if SetWindowLongW(hwnd, GWL_EXSTYLE, (ex_style | WS_EX_NOACTIVATE.0) as i32) == 0 {
+            return Err("Failed to set window style.".to_string());
+        }
+    }
+    Ok(())
+}
+
+/// Hides the window from screenshots and screen recordings while keeping it visible locally.
+/// This is only supported on Windows 10 version 2004 and later.
+pub fn exclude_from_capture(hwnd: HWND) -> Result<(), String> {
+    if unsafe { SetWindowDisplayAffinity(hwnd, WDA_EXCLUDEFROMCAPTURE) }.as_bool() {
+        Ok(())
+    } else {
+        Err("Failed to exclude window from capture.".to_string())
+    }
+}
+
+pub fn get_virtual_screen_rect() -> RECT {
+    unsafe {
+        let left = GetSystemMetrics(SM_XVIRTUALSCREEN);
+        let top = GetSystemMetrics(SM_YVIRTUALSCREEN);
+        RECT {
+            left,
+            top,
+            right: left + GetSystemMetrics(SM_CXVIRTUALSCREEN),
+            bottom: top + GetSystemMetrics(SM_CYVIRTUALSCREEN),
+        }
+    }
+}
+
+fn clamp_to_virtual_screen(point: POINT) -> POINT {
+    let bounds = get_virtual_screen_rect();
+    POINT {
+        x: point
+            .x
+            .clamp(bounds.left, (bounds.right - 1).max(bounds.left)),
+        y: point
+            .y
+            .clamp(bounds.top, (bounds.bottom - 1).max(bounds.top)),
+    }
+}
+
+/// Shifts the position left and up as far as needed to keep the content inside the work area of
+/// its monitor. The content is scaled by the monitor's DPI, since its extent is given in logical pixels.
+fn clamp_to_monitor(point: POINT, content_extent: (f32, f32)) -> POINT {
+    unsafe {
+        let monitor = MonitorFromPoint(point, MONITOR_DEFAULTTONEAREST);
+        let mut info = MONITORINFO {
+            cbSize: std::mem::size_of::<MONITORINFO>() as u32,
+            ..Default::default()
+        };
+        if !GetMonitorInfoW(monitor, &mut info).as_bool() {
+            return point;
+        }
+
+        let (mut dpi_x, mut dpi_y) = (USER_DEFAULT_SCREEN_DPI, USER_DEFAULT_SCREEN_DPI);
+        let _ = GetDpiForMonitor(monitor, MDT_EFFECTIVE_DPI, &mut dpi_x, &mut dpi_y);
+        let width = (content_extent.0 * dpi_x as f32 / USER_DEFAULT_SCREEN_DPI as f32) as i32;
+        let height = (content_extent.1 * dpi_y as f32 / USER_DEFAULT_SCREEN_DPI as f32) as i32;
+
+        // The top left corner wins if the content is larger than the work area
+        let work_area = info.rcWork;
+        POINT {
+            x: point.x.min(work_area.right - width).max(work_area.left),
+            y: point.y.min(work_area.bottom - height).max(work_area.top),
+        }
+    }
+}
+
+pub fn set_display_change_handler(handler: DisplayChangeHandler) {
+    *DISPLAY_CHANGE_HANDLER.lock().unwrap() = Some(handler);
+}
+
+/// Creates a hidden window that receives `WM_DISPLAYCHANGE` broadcasts.
+/// The calling thread needs to run a message loop for the handler to be called.
+pub fn create_display_change_window() -> Result<HWND, String> {
+    let class_name = to_wide_null(DISPLAY_CHANGE_WINDOW_CLASS);
+
+    unsafe {
+        let instance = GetModuleHandleW(None).map_err(|e| e.to_string())?;
+
+        let window_class = WNDCLASSW {
+            lpfnWndProc: Some(display_change_window_proc),
+            hInstance: instance,
+            lpszClassName: PCWSTR::from_raw(class_name.as_ptr()),
+            ..Default::default()
+        };
+        if RegisterClassW(&window_class) == 0 {
+            return Err("Failed to register window class.".to_string());
+        }
+
+        // Message-only windows don't receive broadcasts, so this is a regular window that is never shown
+        let hwnd = CreateWindowExW(
+            WINDOW_EX_STYLE(0),
+            PCWSTR::from_raw(class_name.as_ptr()),
+            PCWSTR::null(),
+            WINDOW_STYLE(0),
+            0,
+            0,
+            0,
+            0,
+            HWND(0),
+            HMENU(0),
+            instance,
+            None,
         );
 
+        if hwnd.0 == 0 {
+            Err("Failed to create window.".to_string())
+        } else {
+            Ok(hwnd)
+        }
+    }
+}
+
+unsafe extern "system" fn display_change_window_proc(
+    hwnd: HWND,
+    msg: u32,
+    w_param: WPARAM,
+    l_param: LPARAM,
+) -> LRESULT {
+    if msg == WM_DISPLAYCHANGE {
+        if let Some(handler) = *DISPLAY_CHANGE_HANDLER.lock().unwrap() {
+            handler();
+        }
+    }
+
+    DefWindowProcW(hwnd, msg, w_param, l_param)
+}
+
+pub fn set_foreground_change_handler(handler: ForegroundChangeHandler) {
+    *FOREGROUND_CHANGE_HANDLER.lock().unwrap() = Some(handler);
+}
+
+/// Listens for windows of other processes being brought to the foreground, e.g. by clicking them.
+/// The calling thread needs to run a message loop for the handler to be called.
+pub fn create_foreground_event_hook() -> Result<(), String> {
+    let hook = unsafe {
+        SetWinEventHook(
+            EVENT_SYSTEM_FOREGROUND,
+            EVENT_SYSTEM_FOREGROUND,
+            HMODULE(0),
+            Some(foreground_event_proc),
+            0,
+            0,
+            WINEVENT_OUTOFCONTEXT | WINEVENT_SKIPOWNPROCESS,
+        )
+    };
+
+    if hook.0 == 0 {
+        return Err("Failed to set foreground event hook.".to_string());
+    }
+    Ok(())
+}
+
+unsafe extern "system" fn foreground_event_proc(
+    _hook: HWINEVENTHOOK,
+    _event: u32,
+    _hwnd: HWND,
+    _id_object: i32,
+    _id_child: i32,
+    _event_thread: u32,
+    _event_time: u32,
+) {
+    if let Some(handler) = *FOREGROUND_CHANGE_HANDLER.lock().unwrap() {
+        handler();
+    }
+}
+
+/// Dispatches the messages that are waiting in the queue of the current thread, without blocking.
+#[cfg(not(feature = "gui"))]
+pub fn dispatch_pending_messages() {
+    let mut msg = MSG::default();
+    while unsafe { PeekMessageW(&mut msg, HWND(0), 0, 0, PM_REMOVE) }.as_bool() {
+        unsafe {
+            TranslateMessage(&msg);
+            DispatchMessageW(&msg);
+        }
+    }
+}
+
+pub fn play_default_sound() {
+    unsafe {
+        MessageBeep(MB_OK);
+    }
+}
+
+/// Returns the executable name of the process that owns the foreground window, e.g. `Code.exe`.
+pub fn get_foreground_process_name() -> Result<String, String> {
+    unsafe {
+        let mut process_id = 0;
+        GetWindowThreadProcessId(GetForegroundWindow(), Some(&mut process_id));
+        if process_id == 0 {
+            return Err("Failed to get foreground process.".to_string());
+        }
+
+        let handle = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, process_id)
+            .map_err(|e| e.to_string())?;
+        let mut buffer = vec![0u16; MAX_PATH as usize];
+        let mut length = buffer.len() as u32;
+        let result = QueryFullProcessImageNameW(
+            handle,
+            PROCESS_NAME_WIN32,
+            PWSTR::from_raw(buffer.as_mut_ptr()),
+            &mut length,
+        );
+        CloseHandle(handle);
+
+        if !result.as_bool() {
+            return Err("Failed to get foreground process name.".to_string());
+        }
+
+        let image_path = String::from_utf16_lossy(&buffer[..length as usize]);
+        Ok(image_path
+            .rsplit('\\')
+            .next()
+            .unwrap_or(&image_path)
+            .to_string())
+    }
+}
+
+/// Returns the target of a Windows shortcut (`.lnk`) file.
+pub fn resolve_shortcut(path: &str) -> Result<String, String> {
+    let file_name = to_wide_null(path);
+
+    unsafe {
+        // Fails if COM has already been initialized differently on this thread, which is fine
+        let initialized = CoInitializeEx(None, COINIT_APARTMENTTHREADED).is_ok();
+
+        let result = (|| {
+            let shell_link: IShellLinkW = CoCreateInstance(&ShellLink, None, CLSCTX_INPROC_SERVER)
+                .map_err(|e| e.to_string())?;
+            let persist_file: IPersistFile = shell_link.cast().map_err(|e| e.to_string())?;
+            persist_file
+                .Load(PCWSTR::from_raw(file_name.as_ptr()), STGM_READ)
+                .map_err(|e| e.to_string())?;
+
+            let mut buffer = vec![0u16; MAX_PATH as usize];
+            shell_link
+                .GetPath(&mut buffer, std::ptr::null_mut(), 0)
+                .map_err(|e| e.to_string())?;
+
+            let length = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+            if length == 0 {
+                // Shortcuts to virtual folders like "This PC" have no file system path
+                return Err("Shortcut has no target path.".to_string());
+            }
+            Ok(String::from_utf16_lossy(&buffer[..length]))
+        })();
+
+        if initialized {
+            CoUninitialize();
+        }
+        result
+    }
+}
+
+/// Opens a file, folder or program through the shell, like double-clicking it in Explorer.
+pub fn shell_execute(file: &str, parameters: &str) -> Result<(), String> {
+    let file = to_wide_null(file);
+    let parameters = to_wide_null(parameters);
+
+    let result = unsafe {
+        ShellExecuteW(
+            HWND(0),
+            PCWSTR::null(),
+            PCWSTR::from_raw(file.as_ptr()),
+            PCWSTR::from_raw(parameters.as_ptr()),
+            PCWSTR::null(),
+            SW_SHOWNORMAL,
+        )
+    };
+
+    // Values greater than 32 indicate success
+    if result.0 > 32 {
         Ok(())
+    } else {
+        Err("Failed to execute shell command.".to_string())
+    }
+}
+
+/// Returns whether Pathte is registered to start at login.
+pub fn is_autostart_enabled() -> bool {
+    let run_key = to_wide_null(RUN_KEY);
+    let value_name = to_wide_null(APP_NAME);
+    let result = unsafe {
+        RegGetValueW(
+            HKEY_CURRENT_USER,
+            PCWSTR(run_key.as_ptr()),
+            PCWSTR(value_name.as_ptr()),
+            RRF_RT_REG_SZ,
+            None,
+            None,
+            None,
+        )
+    };
+    result == ERROR_SUCCESS
+}
+
+/// Registers the current executable to start at login, or removes the registration.
+pub fn set_autostart(enabled: bool) -> Result<(), String> {
+    let run_key = to_wide_null(RUN_KEY);
+    let value_name = to_wide_null(APP_NAME);
+
+    let mut key = HKEY::default();
+    let result = unsafe {
+        RegOpenKeyExW(
+            HKEY_CURRENT_USER,
+            PCWSTR(run_key.as_ptr()),
+            0,
+            KEY_SET_VALUE,
+            &mut key,
+        )
+    };
+    if result != ERROR_SUCCESS {
+        return Err("Failed to open the registry key for starting with Windows.".to_string());
     }
+
+    let result = if enabled {
+        match env::current_exe() {
+            // Quoted, since the path may contain spaces
+            Ok(exe) => {
+                let data: Vec<u8> = to_wide_null(&format!("\"{}\"", exe.display()))
+                    .iter()
+                    .flat_map(|unit| unit.to_le_bytes())
+                    .collect();
+                unsafe { RegSetValueExW(key, PCWSTR(value_name.as_ptr()), 0, REG_SZ, Some(&data)) }
+            }
+            Err(_) => ERROR_FILE_NOT_FOUND,
+        }
+    } else {
+        match unsafe { RegDeleteValueW(key, PCWSTR(value_name.as_ptr())) } {
+            // Not being registered is what was asked for
+            ERROR_FILE_NOT_FOUND => ERROR_SUCCESS,
+            result => result,
+        }
+    };
+    unsafe {
+        RegCloseKey(key);
+    }
+
+    if result == ERROR_SUCCESS {
+        Ok(())
+    } else if enabled {
+        Err("Failed to register Pathte to start with Windows.".to_string())
+    } else {
+        Err("Failed to stop Pathte from starting with Windows.".to_string())
+    }
+}
+
+fn to_wide_null(text: &str) -> Vec<u16> {
+    text.encode_utf16().chain(std::iter::once(0)).collect()
 }